@@ -0,0 +1,542 @@
+use thiserror::Error;
+
+use crate::variables::{VariableError, Variables};
+
+/// Errors from evaluating an arithmetic expression
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ArithmeticError {
+    #[error("{expression}: syntax error: operand expected (error token is \"{token}\")")]
+    OperandExpected { expression: String, token: String },
+    #[error("{expression}: syntax error in expression (error token is \"{token}\")")]
+    Syntax { expression: String, token: String },
+    #[error("{0}: invalid number")]
+    InvalidNumber(String),
+    #[error("{0}: division by 0")]
+    DivisionByZero(String),
+    #[error("{0}: exponent less than 0")]
+    NegativeExponent(String),
+    #[error("{0}: expression recursion level exceeded")]
+    Recursion(String),
+    #[error(transparent)]
+    Variable(#[from] VariableError),
+}
+
+/// Operators, longest first so the tokenizer can match greedily
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=", "/=",
+    "%=", "+=", "-=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~",
+    "?", ":", "=", ",", "(", ")",
+];
+
+/// Left-associative binary operators, from loosest to tightest binding
+const BINARY_LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// How deeply variables may refer to expressions in other variables
+const MAX_RECURSION: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(String),
+    Name(String),
+    Operator(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Variable(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `name = value`, or `name op= value` with the operator given
+    Assign(String, Option<&'static str>, Box<Expr>),
+    /// `++name`, `--name`, `name++` or `name--`
+    Increment {
+        name: String,
+        delta: i64,
+        prefix: bool,
+    },
+}
+
+/// Evaluate an arithmetic expression over shell variables, as `$((...))`, `let` and `((...))` do
+pub fn evaluate(variables: &mut Variables, expression: &str) -> Result<i64, ArithmeticError> {
+    evaluate_nested(variables, expression, 0)
+}
+
+fn evaluate_nested(
+    variables: &mut Variables,
+    expression: &str,
+    depth: usize,
+) -> Result<i64, ArithmeticError> {
+    if expression.trim().is_empty() {
+        return Ok(0);
+    }
+
+    let expr = Parser::new(expression)?.parse()?;
+    Evaluator {
+        variables,
+        expression,
+        depth,
+    }
+    .eval(&expr)
+}
+
+/// Split an expression into tokens, each with the byte offset where it starts
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, ArithmeticError> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.char_indices().peekable();
+
+    while let Some(&(start, c)) = rest.peek() {
+        if c.is_whitespace() {
+            rest.next();
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let number = c.is_ascii_digit();
+            let mut end = start;
+            while let Some(&(i, c)) = rest.peek() {
+                let base_digit = number && matches!(c, '#' | '@');
+                if !(c.is_ascii_alphanumeric() || c == '_' || base_digit) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                rest.next();
+            }
+
+            let text = expression[start..end].to_string();
+            tokens.push(match number {
+                true => (start, Token::Number(text)),
+                false => (start, Token::Name(text)),
+            });
+        } else {
+            let Some(op) = OPERATORS
+                .iter()
+                .find(|op| expression[start..].starts_with(**op))
+            else {
+                return Err(ArithmeticError::Syntax {
+                    expression: expression.to_string(),
+                    token: expression[start..].to_string(),
+                });
+            };
+            for _ in 0..op.len() {
+                rest.next();
+            }
+            tokens.push((start, Token::Operator(op)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parse an integer constant: decimal, octal with a leading `0`, hex with `0x`, or `base#digits`
+fn parse_number(text: &str) -> Result<i64, ArithmeticError> {
+    let invalid = || ArithmeticError::InvalidNumber(text.to_string());
+
+    let (base, digits) = if let Some((base, digits)) = text.split_once('#') {
+        let base = base.parse::<u32>().map_err(|_| invalid())?;
+        if !(2..=64).contains(&base) {
+            return Err(invalid());
+        }
+        (base, digits)
+    } else if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        (16, hex)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+
+    digits.chars().try_fold(0i64, |value, c| {
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            // Above base 36, upper case letters follow the lower case ones
+            'A'..='Z' if base > 36 => c as u32 - 'A' as u32 + 36,
+            'A'..='Z' => c as u32 - 'A' as u32 + 10,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(invalid()),
+        };
+        if digit >= base {
+            return Err(invalid());
+        }
+        Ok(value.wrapping_mul(base as i64).wrapping_add(digit as i64))
+    })
+}
+
+/// Recursive descent parser over the tokens of one expression
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(expression: &'a str) -> Result<Self, ArithmeticError> {
+        Ok(Self {
+            expression,
+            tokens: tokenize(expression)?,
+            pos: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Expr, ArithmeticError> {
+        let expr = self.comma()?;
+        match self.tokens.get(self.pos) {
+            None => Ok(expr),
+            Some(_) => Err(self.syntax_error()),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn peek_operator(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Operator(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = self.peek_operator() == Some(op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Source text from the current token onwards, as shown in error messages
+    fn remaining(&self) -> String {
+        match self.tokens.get(self.pos) {
+            Some(&(start, _)) => self.expression[start..].trim().to_string(),
+            None => String::new(),
+        }
+    }
+
+    fn syntax_error(&self) -> ArithmeticError {
+        ArithmeticError::Syntax {
+            expression: self.expression.trim().to_string(),
+            token: self.remaining(),
+        }
+    }
+
+    fn comma(&mut self) -> Result<Expr, ArithmeticError> {
+        let mut expr = self.assignment()?;
+        while self.eat(",") {
+            let rhs = self.assignment()?;
+            expr = Expr::Binary(",", Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn assignment(&mut self) -> Result<Expr, ArithmeticError> {
+        if let Some(Token::Name(name)) = self.peek()
+            && let Some((_, Token::Operator(op))) = self.tokens.get(self.pos + 1)
+            && op.ends_with('=')
+            && !matches!(*op, "==" | "!=" | "<=" | ">=")
+        {
+            let name = name.clone();
+            let op = op.strip_suffix('=').filter(|op| !op.is_empty());
+            let op = op.and_then(|op| OPERATORS.iter().copied().find(|known| *known == op));
+            self.pos += 2;
+
+            let value = self.assignment()?;
+            return Ok(Expr::Assign(name, op, Box::new(value)));
+        }
+
+        self.conditional()
+    }
+
+    fn conditional(&mut self) -> Result<Expr, ArithmeticError> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+
+        let then = self.comma()?;
+        if !self.eat(":") {
+            return Err(self.syntax_error());
+        }
+        let otherwise = self.assignment()?;
+
+        Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, ArithmeticError> {
+        let Some(operators) = BINARY_LEVELS.get(level) else {
+            return self.power();
+        };
+
+        let mut expr = self.binary(level + 1)?;
+        while let Some(op) = self.peek_operator().filter(|op| operators.contains(op)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn power(&mut self) -> Result<Expr, ArithmeticError> {
+        let base = self.unary()?;
+        if self.eat("**") {
+            let exponent = self.power()?;
+            return Ok(Expr::Binary("**", Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ArithmeticError> {
+        match self.peek_operator() {
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                let Some(Token::Name(name)) = self.peek() else {
+                    return Err(self.operand_expected());
+                };
+                let name = name.clone();
+                self.pos += 1;
+                Ok(Expr::Increment {
+                    name,
+                    delta: if op == "++" { 1 } else { -1 },
+                    prefix: true,
+                })
+            }
+            Some(op @ ("+" | "-" | "!" | "~")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, ArithmeticError> {
+        let expr = self.primary()?;
+        if let Expr::Variable(name) = &expr
+            && let Some(op @ ("++" | "--")) = self.peek_operator()
+        {
+            self.pos += 1;
+            return Ok(Expr::Increment {
+                name: name.clone(),
+                delta: if op == "++" { 1 } else { -1 },
+                prefix: false,
+            });
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ArithmeticError> {
+        match self.peek().cloned() {
+            Some(Token::Number(text)) => {
+                self.pos += 1;
+                Ok(Expr::Number(parse_number(&text)?))
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                Ok(Expr::Variable(name))
+            }
+            Some(Token::Operator("(")) => {
+                self.pos += 1;
+                let expr = self.comma()?;
+                if !self.eat(")") {
+                    return Err(self.syntax_error());
+                }
+                Ok(expr)
+            }
+            _ => Err(self.operand_expected()),
+        }
+    }
+
+    fn operand_expected(&self) -> ArithmeticError {
+        ArithmeticError::OperandExpected {
+            expression: self.expression.trim().to_string(),
+            token: self.remaining(),
+        }
+    }
+}
+
+/// Evaluates a parsed expression, reading and assigning shell variables
+struct Evaluator<'a> {
+    variables: &'a mut Variables,
+    expression: &'a str,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn eval(&mut self, expr: &Expr) -> Result<i64, ArithmeticError> {
+        match expr {
+            Expr::Number(value) => Ok(*value),
+            Expr::Variable(name) => self.variable(name),
+            Expr::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                Ok(match *op {
+                    "-" => value.wrapping_neg(),
+                    "!" => (value == 0) as i64,
+                    "~" => !value,
+                    _ => value,
+                })
+            }
+            Expr::Binary("&&", lhs, rhs) => {
+                Ok((self.eval(lhs)? != 0 && self.eval(rhs)? != 0) as i64)
+            }
+            Expr::Binary("||", lhs, rhs) => {
+                Ok((self.eval(lhs)? != 0 || self.eval(rhs)? != 0) as i64)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                self.apply(op, lhs, rhs)
+            }
+            Expr::Conditional(condition, then, otherwise) => match self.eval(condition)? {
+                0 => self.eval(otherwise),
+                _ => self.eval(then),
+            },
+            Expr::Assign(name, op, value) => {
+                let value = self.eval(value)?;
+                let value = match op {
+                    Some(op) => {
+                        let current = self.variable(name)?;
+                        self.apply(op, current, value)?
+                    }
+                    None => value,
+                };
+                self.variables.set(name, value.to_string())?;
+                Ok(value)
+            }
+            Expr::Increment {
+                name,
+                delta,
+                prefix,
+            } => {
+                let old = self.variable(name)?;
+                let new = old.wrapping_add(*delta);
+                self.variables.set(name, new.to_string())?;
+                Ok(if *prefix { new } else { old })
+            }
+        }
+    }
+
+    fn apply(&self, op: &str, lhs: i64, rhs: i64) -> Result<i64, ArithmeticError> {
+        let division_by_zero = || ArithmeticError::DivisionByZero(self.expression.trim().into());
+
+        Ok(match op {
+            "," => rhs,
+            "|" => lhs | rhs,
+            "^" => lhs ^ rhs,
+            "&" => lhs & rhs,
+            "==" => (lhs == rhs) as i64,
+            "!=" => (lhs != rhs) as i64,
+            "<=" => (lhs <= rhs) as i64,
+            ">=" => (lhs >= rhs) as i64,
+            "<" => (lhs < rhs) as i64,
+            ">" => (lhs > rhs) as i64,
+            "<<" => lhs.wrapping_shl(rhs as u32),
+            ">>" => lhs.wrapping_shr(rhs as u32),
+            "+" => lhs.wrapping_add(rhs),
+            "-" => lhs.wrapping_sub(rhs),
+            "*" => lhs.wrapping_mul(rhs),
+            "/" if rhs == 0 => return Err(division_by_zero()),
+            "/" => lhs.wrapping_div(rhs),
+            "%" if rhs == 0 => return Err(division_by_zero()),
+            "%" => lhs.wrapping_rem(rhs),
+            "**" if rhs < 0 => {
+                return Err(ArithmeticError::NegativeExponent(
+                    self.expression.trim().into(),
+                ));
+            }
+            "**" => lhs.wrapping_pow(rhs.min(u32::MAX as i64) as u32),
+            _ => unreachable!("`{op}` is not a binary operator"),
+        })
+    }
+
+    /// Value of a variable, itself evaluated as an expression; unset or empty counts as 0
+    fn variable(&mut self, name: &str) -> Result<i64, ArithmeticError> {
+        let Some(value) = self.variables.get(name).map(str::to_string) else {
+            return Ok(0);
+        };
+        if let Ok(number) = value.trim().parse() {
+            return Ok(number);
+        }
+        if self.depth >= MAX_RECURSION {
+            return Err(ArithmeticError::Recursion(name.to_string()));
+        }
+
+        evaluate_nested(self.variables, &value, self.depth + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<i64, ArithmeticError> {
+        evaluate(&mut Variables::default(), expression)
+    }
+
+    #[test]
+    fn precedence_and_operators() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(512));
+        assert_eq!(eval("-2 ** 2"), Ok(4));
+        assert_eq!(eval("7 / 2 + 7 % 2"), Ok(4));
+        assert_eq!(eval("1 << 4 | 1"), Ok(17));
+        assert_eq!(eval("~0 ^ 5 & 3"), Ok(-2));
+        assert_eq!(eval("3 > 2 && 2 >= 2 || 0"), Ok(1));
+        assert_eq!(eval("!5 == 0"), Ok(1));
+        assert_eq!(eval("0 ? 1 : 2 ? 3 : 4"), Ok(3));
+        assert_eq!(eval("0x1f + 010 + 2#101"), Ok(44));
+        assert_eq!(eval("1, 2, 3"), Ok(3));
+    }
+
+    #[test]
+    fn assignments_and_increments() {
+        let mut variables = Variables::default();
+        variables.set("n", "5").unwrap();
+        variables.set("expr", "n * 2").unwrap();
+
+        assert_eq!(evaluate(&mut variables, "n++ + ++n"), Ok(12));
+        assert_eq!(variables.get("n"), Some("7"));
+        assert_eq!(evaluate(&mut variables, "n *= 3, n -= 1"), Ok(20));
+        assert_eq!(evaluate(&mut variables, "expr + unset"), Ok(40));
+        assert_eq!(evaluate(&mut variables, "0 && (n = 1)"), Ok(0));
+        assert_eq!(variables.get("n"), Some("20"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            eval("1 / 0"),
+            Err(ArithmeticError::DivisionByZero("1 / 0".to_string()))
+        );
+        assert!(matches!(
+            eval("1 +"),
+            Err(ArithmeticError::OperandExpected { .. })
+        ));
+        assert!(matches!(
+            eval("(1 + 2"),
+            Err(ArithmeticError::Syntax { .. })
+        ));
+        assert_eq!(
+            eval("08"),
+            Err(ArithmeticError::InvalidNumber("08".to_string()))
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use std::io::{self, Write};
+
+use crate::arithmetic::evaluate;
+use crate::shell::Shell;
+
+/// Evaluate each argument as an arithmetic expression, succeeding if the last one is non-zero
+pub fn let_(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    if arguments.is_empty() {
+        eprintln!("let: expression expected");
+        return Ok(1);
+    }
+
+    let mut last = 0;
+    for arg in arguments {
+        match evaluate(&mut shell.variables, arg) {
+            Ok(value) => last = value,
+            Err(err) => {
+                eprintln!("let: {err}");
+                return Ok(1);
+            }
+        }
+    }
+
+    Ok(if last != 0 { 0 } else { 1 })
+}
@@ -0,0 +1,61 @@
+use std::env::{current_dir, set_current_dir};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::shell::Shell;
+
+/// Change the working directory, to `HOME` without an argument, keeping `PWD` and `OLDPWD`
+pub fn cd(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let new_arg = match arguments.first() {
+        Some(dir) => dir.clone(),
+        None => match shell.variables.get("HOME") {
+            Some(home) => home.to_string(),
+            None => {
+                eprintln!("cd: HOME not set");
+                return Ok(1);
+            }
+        },
+    };
+    let new_dir = Path::new(&new_arg).to_path_buf();
+    let old_pwd = match shell.variables.get("PWD") {
+        Some(pwd) => Some(pwd.to_string()),
+        None => current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().into_owned()),
+    };
+
+    match set_current_dir(new_dir) {
+        Ok(_) => {
+            let pwd = current_dir()?.to_string_lossy().into_owned();
+            // Failing to update read-only PWD or OLDPWD does not undo the change
+            if let Some(old_pwd) = old_pwd {
+                let _ = shell.variables.set("OLDPWD", old_pwd);
+            }
+            let _ = shell.variables.set("PWD", pwd);
+            Ok(0)
+        }
+        Err(_) => {
+            writeln!(out, "cd: {}: No such file or directory", new_arg)?;
+            Ok(1)
+        }
+    }
+}
+
+/// Print the working directory
+pub fn pwd(_shell: &mut Shell, _arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    writeln!(out, "{}", current_dir()?.to_str().unwrap())?;
+    Ok(0)
+}
+
+/// `.`, which stays in the working directory
+pub fn current(_shell: &mut Shell, _arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    set_current_dir(current_dir()?)?;
+    Ok(0)
+}
+
+/// `..`
+pub fn parent(_shell: &mut Shell, _arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    let new_dir = current_dir()?.pop().to_string();
+    set_current_dir(new_dir)?;
+    Ok(0)
+}
@@ -0,0 +1,9 @@
+use std::io::{self, Write};
+
+use crate::shell::Shell;
+
+/// Print the arguments separated by spaces
+pub fn echo(_shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    writeln!(out, "{}", arguments.join(" ").trim())?;
+    Ok(0)
+}
@@ -0,0 +1,47 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write, stdout};
+
+use crate::executor::run_exit_trap;
+use crate::history::get_history;
+use crate::shell::Shell;
+
+/// Exit the shell with the given status, or the last command's, after running the EXIT
+/// trap and, in an interactive session, appending its commands to `HISTFILE`
+pub fn exit(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    if arguments.len() > 1 {
+        eprintln!("exit: too many arguments");
+        return Ok(1);
+    }
+
+    let status = match arguments.first().map(|arg| arg.parse::<i64>()) {
+        None => shell.last_status,
+        Some(Ok(status)) => (status & 0xff) as i32,
+        Some(Err(_)) => {
+            eprintln!("exit: {}: numeric argument required", arguments[0]);
+            2
+        }
+    };
+
+    shell.last_status = status;
+    run_exit_trap(shell);
+
+    stdout().flush()?;
+    if shell.subshell || !shell.interactive {
+        std::process::exit(status);
+    }
+
+    let file_path = std::env::var_os("HISTFILE");
+    let existing_history_len = get_history().len();
+
+    if let Some(file_path) = file_path {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(file_path)?;
+
+        file.write_all(shell.history[existing_history_len..].join("\n").as_bytes())?;
+        file.write_all("\n".as_bytes())?;
+    }
+
+    std::process::exit(status);
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Write};
+
+use crate::shell::{Flow, Shell};
+
+/// Leave the innermost loop, or the `n` innermost loops
+pub fn break_(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    leave_loops(shell, arguments, "break", Flow::Break)
+}
+
+/// Start the next iteration of the innermost loop, or of the `n`th innermost loop
+pub fn continue_(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    leave_loops(shell, arguments, "continue", Flow::Continue)
+}
+
+/// Leave the running function with the given status, or the last command's
+pub fn return_(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    if shell.function_depth == 0 {
+        eprintln!("return: can only `return' from a function");
+        return Ok(1);
+    }
+    if arguments.len() > 1 {
+        eprintln!("return: too many arguments");
+        return Ok(1);
+    }
+
+    let status = match arguments.first().map(|arg| arg.parse::<i64>()) {
+        None => shell.last_status,
+        Some(Ok(status)) => (status & 0xff) as i32,
+        Some(Err(_)) => {
+            eprintln!("return: {}: numeric argument required", arguments[0]);
+            2
+        }
+    };
+
+    shell.flow = Some(Flow::Return);
+    Ok(status)
+}
+
+/// Shared implementation of `break` and `continue`: check the loop count and record
+/// the flow for the enclosing loops to act on
+fn leave_loops(
+    shell: &mut Shell,
+    arguments: &[String],
+    builtin: &str,
+    flow: fn(usize) -> Flow,
+) -> io::Result<i32> {
+    if arguments.len() > 1 {
+        eprintln!("{builtin}: too many arguments");
+        return Ok(1);
+    }
+
+    let count = match arguments.first().map(|arg| arg.parse::<usize>()) {
+        None => 1,
+        Some(Ok(count)) if count > 0 => count,
+        Some(Ok(_)) => {
+            eprintln!("{builtin}: {}: loop count out of range", arguments[0]);
+            return Ok(1);
+        }
+        Some(Err(_)) => {
+            eprintln!("{builtin}: {}: numeric argument required", arguments[0]);
+            return Ok(1);
+        }
+    };
+
+    if shell.loop_depth == 0 {
+        eprintln!("{builtin}: only meaningful in a `for', `while', or `until' loop");
+        return Ok(0);
+    }
+
+    // Asking for more loops than there are leaves all of them
+    shell.flow = Some(flow(count.min(shell.loop_depth)));
+    Ok(0)
+}
@@ -0,0 +1,85 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use crate::shell::Shell;
+
+/// List the command history, or read (`-r`), write (`-w`) or append (`-a`) it to a file
+pub fn history(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let local_history = &mut shell.history;
+    let mut history_size: usize = local_history.len();
+    let mut skip_print = false;
+
+    if !arguments.is_empty() {
+        let arg = &arguments[0];
+
+        if arg == "-r" && arguments.len() > 1 {
+            let file = &arguments[1];
+            let file_content = std::fs::read_to_string(file)?;
+
+            let file_content = file_content.lines().collect::<Vec<&str>>();
+            local_history.extend(file_content.iter().map(ToString::to_string));
+
+            skip_print = true;
+        } else if arg == "-w" && arguments.len() > 1 {
+            let file = &arguments[1];
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(file)?;
+
+            file.write_all(local_history.join("\n").as_bytes())?;
+            file.write_all("\n".as_bytes())?;
+
+            skip_print = true;
+        } else if arg == "-a" && arguments.len() > 1 {
+            let file_name = &arguments[1];
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file_name)?;
+
+            let search_str = format!("history -a {}", file_name);
+
+            let history_slice = local_history
+                .iter()
+                .rev()
+                .enumerate()
+                .filter(|&(_, s)| *s == search_str)
+                .take(2)
+                .map(|(i, _)| local_history.len() - i)
+                .collect::<Vec<usize>>();
+
+            //as it is reversed, we need to slice reversed, if it contains one occurrence
+            //then it happened once
+
+            if history_slice.len() > 1 {
+                file.write_all(
+                    local_history[history_slice[1]..history_slice[0]]
+                        .join("\n")
+                        .as_bytes(),
+                )?;
+                file.write_all("\n".as_bytes())?;
+            } else {
+                file.write_all(local_history[..].join("\n").as_bytes())?;
+                file.write_all("\n".as_bytes())?;
+            }
+            skip_print = true;
+        }
+
+        history_size = arg.parse::<usize>().unwrap_or(local_history.len());
+    }
+
+    let history_skip = if history_size > local_history.len() {
+        0
+    } else {
+        local_history.len() - history_size
+    };
+
+    if !skip_print {
+        for (i, cmd) in local_history.iter().enumerate().skip(history_skip) {
+            writeln!(out, "    {} {}", i + 1, cmd)?;
+        }
+    }
+    Ok(0)
+}
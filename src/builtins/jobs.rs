@@ -0,0 +1,193 @@
+use std::io::{self, Write};
+
+use crate::jobs::{ProcessState, job_line};
+use crate::shell::Shell;
+
+/// List jobs with their state, only their process IDs with `-p`, or with process IDs
+/// added with `-l`
+pub fn jobs(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let mut long = false;
+    let mut pids_only = false;
+    let mut specs = Vec::new();
+
+    for arg in arguments {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'l' => long = true,
+                        'p' => pids_only = true,
+                        _ => {
+                            eprintln!("jobs: -{flag}: invalid option");
+                            eprintln!("jobs: usage: jobs [-lp] [jobspec ...]");
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => specs.push(arg.as_str()),
+        }
+    }
+
+    shell.jobs.update()?;
+    let mut status = 0;
+    let ids = match specs.is_empty() {
+        true => shell.jobs.ids(),
+        false => specs
+            .into_iter()
+            .filter_map(|spec| match shell.jobs.resolve(Some(spec)) {
+                Ok(id) => Some(id),
+                Err(err) => {
+                    eprintln!("jobs: {err}");
+                    status = 1;
+                    None
+                }
+            })
+            .collect(),
+    };
+
+    for id in ids {
+        let job = shell.jobs.get(id).unwrap();
+        if pids_only {
+            writeln!(out, "{}", job.pid())?;
+            continue;
+        }
+
+        writeln!(out, "{}", job_line(job, shell.jobs.marker(id), long))?;
+        // A finished job is reported once
+        if job.is_finished() {
+            shell.jobs.remove(id);
+        }
+    }
+
+    Ok(status)
+}
+
+/// Bring a job to the foreground, continuing it if it is stopped, and wait for it
+pub fn fg(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let id = match shell.jobs.resolve(arguments.first().map(String::as_str)) {
+        Ok(id) => id,
+        Err(err) => {
+            eprintln!("fg: {err}");
+            return Ok(1);
+        }
+    };
+
+    let job = shell.jobs.get_mut(id).unwrap();
+    writeln!(out, "{}", job.command)?;
+    out.flush()?;
+    if let (Some(terminal), Some(pgid)) = (shell.terminal, job.pgid) {
+        terminal.give(pgid);
+    }
+    job.resume()?;
+
+    let waited = shell.jobs.wait_for(id);
+    if let Some(terminal) = shell.terminal {
+        terminal.reclaim();
+    }
+    let state = waited?;
+    if state == ProcessState::Stopped {
+        let job = shell.jobs.get(id).unwrap();
+        eprintln!("\n{}", job_line(job, shell.jobs.marker(id), false));
+    }
+    Ok(state.status())
+}
+
+/// Continue stopped jobs in the background
+pub fn bg(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let specs = match arguments.is_empty() {
+        true => vec![None],
+        false => arguments.iter().map(|arg| Some(arg.as_str())).collect(),
+    };
+    let mut status = 0;
+
+    for spec in specs {
+        let id = match shell.jobs.resolve(spec) {
+            Ok(id) => id,
+            Err(err) => {
+                eprintln!("bg: {err}");
+                status = 1;
+                continue;
+            }
+        };
+
+        let job = shell.jobs.get_mut(id).unwrap();
+        if job.state() != ProcessState::Stopped {
+            eprintln!("bg: job {id} already in background");
+            continue;
+        }
+        job.resume()?;
+
+        shell.jobs.touch(id);
+        let job = shell.jobs.get(id).unwrap();
+        writeln!(out, "[{id}]{} {} &", shell.jobs.marker(id), job.command)?;
+    }
+
+    Ok(status)
+}
+
+/// Wait for the given jobs or process IDs, or for every job, and return the status of
+/// the last one waited for
+pub fn wait(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    if arguments.is_empty() {
+        for id in shell.jobs.ids() {
+            shell.jobs.wait_for(id)?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in arguments {
+        let id = if arg.starts_with('%') {
+            shell.jobs.resolve(Some(arg)).map_err(|err| (err, 127))
+        } else {
+            match arg.parse::<i32>() {
+                Ok(pid) => shell
+                    .jobs
+                    .find_pid(pid)
+                    .ok_or_else(|| (format!("pid {pid} is not a child of this shell"), 127)),
+                Err(_) => Err((format!("`{arg}': not a pid or valid job spec"), 2)),
+            }
+        };
+
+        status = match id {
+            Ok(id) => shell.jobs.wait_for(id)?.status(),
+            Err((err, err_status)) => {
+                eprintln!("wait: {err}");
+                err_status
+            }
+        };
+    }
+
+    Ok(status)
+}
+
+/// Remove jobs from the table, so the shell no longer reports or waits for them
+pub fn disown(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    if arguments.iter().any(|arg| arg == "-a") {
+        for id in shell.jobs.ids() {
+            shell.jobs.remove(id);
+        }
+        return Ok(0);
+    }
+
+    let specs = match arguments.is_empty() {
+        true => vec![None],
+        false => arguments.iter().map(|arg| Some(arg.as_str())).collect(),
+    };
+    let mut status = 0;
+
+    for spec in specs {
+        match shell.jobs.resolve(spec) {
+            Ok(id) => {
+                shell.jobs.remove(id);
+            }
+            Err(err) => {
+                eprintln!("disown: {err}");
+                status = 1;
+            }
+        }
+    }
+
+    Ok(status)
+}
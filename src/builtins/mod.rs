@@ -1,3 +1,100 @@
+mod arithmetic;
+mod dirs;
+mod echo;
+mod exit;
+mod flow;
+mod history;
+mod jobs;
+mod set;
+mod shift;
+mod shopt;
+mod trap;
+mod type_;
+mod vars;
+
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Write, stdout};
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+
+pub use arithmetic::let_;
+pub use dirs::{cd, current, parent, pwd};
+pub use echo::echo;
+pub use exit::exit;
+pub use flow::{break_, continue_, return_};
+pub use history::history;
+pub use jobs::{bg, disown, fg, jobs, wait};
+pub use set::set;
+pub use shift::shift;
+pub use shopt::shopt;
+pub use trap::trap;
+pub use type_::type_;
+pub use vars::{export, local, readonly, unset};
+
+use crate::shell::Shell;
+use crate::utils::io_error_message;
+
 /// List of valid built-in shell commands
-pub const VALID_COMMANDS_BUILTIN: &[&str] =
-    &["echo", "exit", "type", "pwd", "cd", "history", ".", ".."];
+pub const VALID_COMMANDS_BUILTIN: &[&str] = &[
+    "echo", "exit", "type", "pwd", "cd", "history", "set", "export", "unset", "readonly", "let",
+    "shopt", "jobs", "fg", "bg", "wait", "disown", "trap", "break", "continue", "return", "local",
+    "shift", ".", "..",
+];
+
+/// A builtin takes the shell, its arguments and its standard output, and returns its status
+type Builtin = fn(&mut Shell, &[String], &mut dyn Write) -> io::Result<i32>;
+
+/// Run the builtin called `name` inside the shell, or return `None` if there is no such
+/// builtin. Builtins write to the shell's own standard streams, so whatever redirections
+/// or pipes are in place on those descriptors apply to them too.
+pub fn run_builtin(shell: &mut Shell, name: &str, arguments: &[String]) -> Option<i32> {
+    let builtin: Builtin = match name {
+        "echo" => echo,
+        "exit" => exit,
+        "type" => type_,
+        "pwd" => pwd,
+        "cd" => cd,
+        "history" => history,
+        "set" => set,
+        "export" => export,
+        "unset" => unset,
+        "readonly" => readonly,
+        "let" => let_,
+        "shopt" => shopt,
+        "jobs" => jobs,
+        "fg" => fg,
+        "bg" => bg,
+        "wait" => wait,
+        "disown" => disown,
+        "trap" => trap,
+        "break" => break_,
+        "continue" => continue_,
+        "return" => return_,
+        "local" => local,
+        "shift" => shift,
+        "." => current,
+        ".." => parent,
+        _ => return None,
+    };
+
+    // Output is buffered per builtin rather than in Rust's process-wide stdout, which
+    // would keep whatever failed to reach a closed pipe and write it somewhere else later
+    let _ = stdout().flush();
+    let fd = ManuallyDrop::new(unsafe { File::from_raw_fd(libc::STDOUT_FILENO) });
+    let mut out = BufWriter::new(&*fd);
+
+    let result = builtin(shell, arguments, &mut out).and_then(|status| {
+        out.flush()?;
+        Ok(status)
+    });
+
+    Some(match result {
+        Ok(status) => status,
+        // A reader that went away ends the builtin the way SIGPIPE ends a process
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => 128 + libc::SIGPIPE,
+        Err(err) => {
+            eprintln!("rsh: {name}: {}", io_error_message(&err));
+            1
+        }
+    })
+}
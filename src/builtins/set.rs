@@ -0,0 +1,71 @@
+use std::io::{self, Write};
+
+use crate::shell::{Options, Shell};
+use crate::utils::shell_quote;
+
+/// Single-letter flags accepted by `set` and the options they stand for
+const FLAGS: &[(char, &str)] = &[('C', "noclobber"), ('f', "noglob")];
+
+/// Toggle shell options with `set -o name`, `set +o name` or a flag such as `set -f`,
+/// and replace the positional parameters with the arguments after the options or `--`;
+/// without arguments, list variables
+pub fn set(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    if arguments.is_empty() {
+        for (name, variable) in shell.variables.iter() {
+            if let Some(value) = &variable.value {
+                writeln!(out, "{name}={}", shell_quote(value))?;
+            }
+        }
+        return Ok(0);
+    }
+
+    let mut arguments = arguments.iter();
+
+    while let Some(arg) = arguments.next() {
+        let enable = match arg.as_str() {
+            "--" => {
+                shell.positional = arguments.cloned().collect();
+                return Ok(0);
+            }
+            "-o" => true,
+            "+o" => false,
+            _ if arg.len() > 1 && arg.starts_with(['-', '+']) => {
+                for flag in arg[1..].chars() {
+                    let Some((_, name)) = FLAGS.iter().find(|(c, _)| *c == flag) else {
+                        eprintln!("set: {}{flag}: invalid option", &arg[..1]);
+                        return Ok(2);
+                    };
+                    shell.options.set(name, arg.starts_with('-'));
+                }
+                continue;
+            }
+            _ if !arg.starts_with(['-', '+']) => {
+                shell.positional = std::iter::once(arg).chain(arguments).cloned().collect();
+                return Ok(0);
+            }
+            _ => {
+                eprintln!("set: {arg}: invalid option");
+                return Ok(2);
+            }
+        };
+
+        let Some(name) = arguments.next() else {
+            for name in Options::NAMES {
+                let value = shell.options.get(name).unwrap();
+                if enable {
+                    writeln!(out, "{:<15}\t{}", name, if value { "on" } else { "off" })?;
+                } else {
+                    writeln!(out, "set {}o {}", if value { "-" } else { "+" }, name)?;
+                }
+            }
+            continue;
+        };
+
+        if !shell.options.set(name, enable) {
+            eprintln!("set: {name}: invalid option name");
+            return Ok(2);
+        }
+    }
+
+    Ok(0)
+}
@@ -0,0 +1,22 @@
+use std::io::{self, Write};
+
+use crate::shell::Shell;
+
+/// Drop the first positional parameters, one unless a count is given, renumbering the rest
+pub fn shift(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    let count = match arguments.first().map(|arg| arg.parse::<usize>()) {
+        None => 1,
+        Some(Ok(count)) => count,
+        Some(Err(_)) => {
+            eprintln!("shift: {}: numeric argument required", arguments[0]);
+            return Ok(1);
+        }
+    };
+
+    if count > shell.positional.len() {
+        return Ok(1);
+    }
+
+    shell.positional.drain(..count);
+    Ok(0)
+}
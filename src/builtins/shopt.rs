@@ -0,0 +1,69 @@
+use std::io::{self, Write};
+
+use crate::shell::{Shell, Shopt};
+
+/// Enable (`-s`) or disable (`-u`) shell options, or report their state
+pub fn shopt(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let mut enable = None;
+    let mut print = false;
+    let mut quiet = false;
+    let mut names = Vec::new();
+
+    for arg in arguments {
+        match arg.as_str() {
+            "-s" => enable = Some(true),
+            "-u" => enable = Some(false),
+            "-p" => print = true,
+            "-q" => quiet = true,
+            _ if arg.starts_with('-') => {
+                eprintln!("shopt: {arg}: invalid option");
+                eprintln!("shopt: usage: shopt [-pqsu] [optname ...]");
+                return Ok(2);
+            }
+            _ => names.push(arg.as_str()),
+        }
+    }
+
+    for name in &names {
+        if shell.shopt.get(name).is_none() {
+            eprintln!("shopt: {name}: invalid shell option name");
+            return Ok(1);
+        }
+    }
+
+    if let Some(value) = enable
+        && !names.is_empty()
+    {
+        for name in names {
+            shell.shopt.set(name, value);
+        }
+        return Ok(0);
+    }
+
+    // Without names, list every option, or only those matching `-s`/`-u`
+    let listed = match names.is_empty() {
+        true => Shopt::NAMES.to_vec(),
+        false => names,
+    };
+    let mut status = 0;
+
+    for name in listed {
+        let value = shell.shopt.get(name).unwrap();
+        if enable.is_some_and(|enable| enable != value) {
+            continue;
+        }
+        if !value {
+            status = 1;
+        }
+
+        if quiet {
+            continue;
+        } else if print {
+            writeln!(out, "shopt {} {name}", if value { "-s" } else { "-u" })?;
+        } else {
+            writeln!(out, "{name:<15}\t{}", if value { "on" } else { "off" })?;
+        }
+    }
+
+    Ok(if enable.is_some() { 0 } else { status })
+}
@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+
+use crate::shell::Shell;
+use crate::signals;
+use crate::traps::Trap;
+use crate::utils::io_error_message;
+
+/// Set actions to run when the shell receives signals or reaches the pseudo-signals
+/// EXIT, ERR, DEBUG and RETURN; `-` resets them, `-p` prints them and `-l` lists signals
+pub fn trap(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let mut arguments = arguments;
+
+    match arguments.first().map(String::as_str) {
+        Some("-l") => {
+            let mut signals = signals::NAMES.to_vec();
+            signals.sort_by_key(|&(_, signal)| signal);
+            for (i, (name, signal)) in signals.iter().enumerate() {
+                let separator = if i % 5 == 4 || i == signals.len() - 1 {
+                    "\n"
+                } else {
+                    "\t"
+                };
+                write!(out, "{signal:2}) SIG{name}{separator}")?;
+            }
+            return Ok(0);
+        }
+        Some("-p") => return print(shell, &arguments[1..], out),
+        Some("--") => arguments = &arguments[1..],
+        Some(arg) if arg.len() > 1 && arg.starts_with('-') => {
+            eprintln!("trap: {arg}: invalid option");
+            eprintln!("trap: usage: trap [-lp] [[action] signal_spec ...]");
+            return Ok(2);
+        }
+        _ => {}
+    }
+
+    let (action, specs) = match arguments {
+        [] => return print(shell, &[], out),
+        // A lone signal specification resets it, like `trap - spec`
+        [spec] => ("-", std::slice::from_ref(spec)),
+        [action, specs @ ..] => (action.as_str(), specs),
+    };
+
+    let interactive = shell.terminal.is_some();
+    let mut status = 0;
+    for spec in specs {
+        let Some(trap) = Trap::parse(spec) else {
+            eprintln!("trap: {spec}: invalid signal specification");
+            status = 1;
+            continue;
+        };
+
+        if action == "-" {
+            shell.traps.reset(trap, interactive);
+        } else if let Err(err) = shell.traps.set(trap, action.to_string()) {
+            eprintln!("trap: {spec}: {}", io_error_message(&err));
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Print the given traps, or all of them, as commands that would set them again
+fn print(shell: &Shell, specs: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let mut traps = Vec::new();
+    let mut status = 0;
+    for spec in specs {
+        match Trap::parse(spec) {
+            Some(trap) => traps.push(trap),
+            None => {
+                eprintln!("trap: {spec}: invalid signal specification");
+                status = 1;
+            }
+        }
+    }
+
+    for (trap, action) in shell.traps.iter() {
+        if specs.is_empty() || traps.contains(&trap) {
+            writeln!(out, "trap -- '{}' {trap}", action.replace('\'', "'\\''"))?;
+        }
+    }
+
+    Ok(status)
+}
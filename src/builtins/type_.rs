@@ -0,0 +1,25 @@
+use std::io::{self, Write};
+
+use super::VALID_COMMANDS_BUILTIN;
+use crate::shell::Shell;
+
+/// Report whether a name is a function, a builtin or the path it runs from
+pub fn type_(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    let name = arguments.join(" ");
+    let name = name.trim();
+
+    if let Some(function) = shell.functions.get(name) {
+        writeln!(out, "{name} is a function")?;
+        writeln!(out, "{function}")?;
+        Ok(0)
+    } else if VALID_COMMANDS_BUILTIN.contains(&name) {
+        writeln!(out, "{name} is a shell builtin")?;
+        Ok(0)
+    } else if let Some(path) = shell.find_executable(name) {
+        writeln!(out, "{name} is {}", path.to_str().unwrap())?;
+        Ok(0)
+    } else {
+        writeln!(out, "{name}: not found")?;
+        Ok(1)
+    }
+}
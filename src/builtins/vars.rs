@@ -0,0 +1,141 @@
+use std::io::{self, Write};
+
+use crate::shell::Shell;
+use crate::utils::is_valid_name;
+use crate::variables::{Variable, VariableError};
+
+/// Mark variables for export to child processes, optionally assigning them
+pub fn export(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    declare(
+        shell,
+        arguments,
+        out,
+        "export",
+        |variable| variable.exported,
+        |shell, name, value| shell.variables.export(name, value),
+    )
+}
+
+/// Mark variables as read-only, optionally assigning them
+pub fn readonly(shell: &mut Shell, arguments: &[String], out: &mut dyn Write) -> io::Result<i32> {
+    declare(
+        shell,
+        arguments,
+        out,
+        "readonly",
+        |variable| variable.readonly,
+        |shell, name, value| shell.variables.set_readonly(name, value),
+    )
+}
+
+/// Remove variables from the shell, or functions with `-f`; without either flag, a name
+/// that is not a variable removes the function of that name
+pub fn unset(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    let mut status = 0;
+    let functions = arguments.iter().any(|arg| arg == "-f");
+    let variables = arguments.iter().any(|arg| arg == "-v");
+
+    for name in arguments
+        .iter()
+        .filter(|arg| !matches!(arg.as_str(), "-f" | "-v"))
+    {
+        let is_variable = shell.variables.variable(name).is_some();
+        if functions || (!variables && !is_variable) {
+            shell.functions.remove(name.as_str());
+        } else if let Err(err) = shell.variables.unset(name) {
+            eprintln!("unset: {err}");
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Declare variables local to the running function, optionally assigning them
+pub fn local(shell: &mut Shell, arguments: &[String], _out: &mut dyn Write) -> io::Result<i32> {
+    if shell.function_depth == 0 {
+        eprintln!("local: can only be used in a function");
+        return Ok(1);
+    }
+
+    let mut status = 0;
+    for arg in arguments {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+
+        if !is_valid_name(name) {
+            eprintln!("local: `{arg}': not a valid identifier");
+            status = 1;
+            continue;
+        }
+
+        if let Err(err) = shell.variables.local(name, value) {
+            eprintln!("local: {err}");
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Shared implementation of `export` and `readonly`: list matching variables without
+/// arguments, otherwise apply the attribute to each `NAME` or `NAME=value`
+fn declare(
+    shell: &mut Shell,
+    arguments: &[String],
+    out: &mut dyn Write,
+    builtin: &str,
+    has_attribute: fn(&Variable) -> bool,
+    apply: fn(&mut Shell, &str, Option<String>) -> Result<(), VariableError>,
+) -> io::Result<i32> {
+    let arguments = arguments
+        .iter()
+        .filter(|arg| arg.as_str() != "-p")
+        .collect::<Vec<_>>();
+
+    if arguments.is_empty() {
+        let flag = if builtin == "export" { "-x" } else { "-r" };
+        for (name, variable) in shell.variables.iter().filter(|(_, v)| has_attribute(v)) {
+            match &variable.value {
+                Some(value) => writeln!(out, "declare {flag} {name}=\"{}\"", escape(value))?,
+                None => writeln!(out, "declare {flag} {name}")?,
+            }
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in arguments {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+
+        if !is_valid_name(name) {
+            eprintln!("{builtin}: `{arg}': not a valid identifier");
+            status = 1;
+            continue;
+        }
+
+        if let Err(err) = apply(shell, name, value) {
+            eprintln!("{builtin}: {err}");
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Escape a value for display inside double quotes
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
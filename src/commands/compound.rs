@@ -0,0 +1,299 @@
+use std::io;
+
+use crate::executor::{Group, execute_list, exit_if_fatal, fork_shell};
+use crate::expansion::{expand_arithmetic, expand_word, expand_words, pattern_matches};
+use crate::jobs::{Process, ProcessState, wait_foreground};
+use crate::parser::ast::{CaseItem, CaseTerminator, CompoundCommand, List, Redirection, Word};
+use crate::redirection::Redirections;
+use crate::shell::{Flow, Shell};
+
+/// Execute a compound command with its redirections applied for its duration, and
+/// return its exit status
+pub fn execute_compound(
+    compound: &CompoundCommand,
+    redirections: &[Redirection],
+    shell: &mut Shell,
+) -> io::Result<i32> {
+    let redirected = Redirections::open(redirections, shell).and_then(Redirections::apply);
+    let _saved_fds = match redirected {
+        Ok(saved_fds) => saved_fds,
+        Err(err) => {
+            eprintln!("rsh: {err}");
+            return Ok(1);
+        }
+    };
+
+    match compound {
+        CompoundCommand::Arithmetic(expression) => Ok(match expand_arithmetic(shell, expression) {
+            Ok(0) => 1,
+            Ok(_) => 0,
+            Err(err) => {
+                eprintln!("rsh: {err}");
+                1
+            }
+        }),
+        CompoundCommand::If {
+            conditionals,
+            else_branch,
+        } => execute_if(conditionals, else_branch.as_ref(), shell),
+        CompoundCommand::While { condition, body } => execute_while(condition, body, true, shell),
+        CompoundCommand::Until { condition, body } => execute_while(condition, body, false, shell),
+        CompoundCommand::For {
+            variable,
+            words,
+            body,
+        } => execute_for(variable, words.as_deref(), body, shell),
+        CompoundCommand::ArithmeticFor {
+            init,
+            condition,
+            step,
+            body,
+        } => execute_arithmetic_for([init, condition, step], body, shell),
+        CompoundCommand::Case { word, items } => execute_case(word, items, shell),
+        CompoundCommand::BraceGroup(body) => {
+            execute_list(body, shell)?;
+            Ok(shell.last_status)
+        }
+        CompoundCommand::Subshell(body) => execute_subshell(body, compound.to_string(), shell),
+    }
+}
+
+/// Run a list in a forked copy of the shell, so that its changes to the working
+/// directory, variables, functions and traps stay in the copy
+fn execute_subshell(body: &List, command: String, shell: &mut Shell) -> io::Result<i32> {
+    let pid = fork_shell(shell, Group::Job(None), &[], |shell| {
+        execute_list(body, shell)?;
+        Ok(shell.last_status)
+    })?;
+
+    let pgid = shell.terminal.map(|terminal| {
+        terminal.give(pid);
+        pid
+    });
+    let process = Process {
+        pid,
+        state: ProcessState::Running,
+    };
+    Ok(wait_foreground(shell, vec![process], pgid, command)?[0])
+}
+
+/// Run a list as a condition and report whether it succeeded
+fn run_condition(condition: &List, shell: &mut Shell) -> io::Result<bool> {
+    let in_condition = std::mem::replace(&mut shell.in_condition, true);
+    let result = execute_list(condition, shell);
+    shell.in_condition = in_condition;
+
+    result.map(|_| shell.last_status == 0)
+}
+
+/// Run the body of the first branch whose condition succeeds, or the `else` branch
+fn execute_if(
+    conditionals: &[(List, List)],
+    else_branch: Option<&List>,
+    shell: &mut Shell,
+) -> io::Result<i32> {
+    for (condition, body) in conditionals {
+        if run_condition(condition, shell)? {
+            execute_list(body, shell)?;
+            return Ok(shell.last_status);
+        }
+        if shell.flow.is_some() {
+            return Ok(shell.last_status);
+        }
+    }
+
+    match else_branch {
+        Some(body) => {
+            execute_list(body, shell)?;
+            Ok(shell.last_status)
+        }
+        None => Ok(0),
+    }
+}
+
+/// Repeat the body while the condition succeeds, or with `until`, while it fails
+fn execute_while(
+    condition: &List,
+    body: &List,
+    while_success: bool,
+    shell: &mut Shell,
+) -> io::Result<i32> {
+    in_loop(shell, |shell| {
+        let mut status = 0;
+        loop {
+            let succeeded = run_condition(condition, shell)?;
+            if leaves_loop(shell) || succeeded != while_success {
+                break;
+            }
+
+            execute_list(body, shell)?;
+            status = shell.last_status;
+            if leaves_loop(shell) {
+                break;
+            }
+        }
+        Ok(status)
+    })
+}
+
+/// Run the body once for each expanded word, assigned to the loop variable
+fn execute_for(
+    variable: &str,
+    words: Option<&[Word]>,
+    body: &List,
+    shell: &mut Shell,
+) -> io::Result<i32> {
+    let words = match words {
+        Some(words) => match expand_words(shell, words) {
+            Ok(words) => words,
+            Err(err) => {
+                exit_if_fatal(&err, shell);
+                eprintln!("rsh: {err}");
+                return Ok(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    in_loop(shell, |shell| {
+        let mut status = 0;
+        for word in words {
+            if let Err(err) = shell.variables.set(variable, word) {
+                eprintln!("rsh: {err}");
+                return Ok(1);
+            }
+
+            execute_list(body, shell)?;
+            status = shell.last_status;
+            if leaves_loop(shell) {
+                break;
+            }
+        }
+        Ok(status)
+    })
+}
+
+/// Run a C-style `for (( init; condition; step ))` loop, where an empty condition is true
+fn execute_arithmetic_for(
+    [init, condition, step]: [&String; 3],
+    body: &List,
+    shell: &mut Shell,
+) -> io::Result<i32> {
+    let evaluate = |shell: &mut Shell, expression: &str| {
+        if expression.is_empty() {
+            return Some(1);
+        }
+        expand_arithmetic(shell, expression)
+            .inspect_err(|err| eprintln!("rsh: {err}"))
+            .ok()
+    };
+
+    if evaluate(shell, init).is_none() {
+        return Ok(1);
+    }
+
+    in_loop(shell, |shell| {
+        let mut status = 0;
+        loop {
+            match evaluate(shell, condition) {
+                None => return Ok(1),
+                Some(0) => break,
+                Some(_) => {}
+            }
+
+            execute_list(body, shell)?;
+            status = shell.last_status;
+            if leaves_loop(shell) {
+                break;
+            }
+
+            if evaluate(shell, step).is_none() {
+                return Ok(1);
+            }
+        }
+        Ok(status)
+    })
+}
+
+/// Run the arm whose patterns match the expanded word, then act on its terminator
+fn execute_case(word: &Word, items: &[CaseItem], shell: &mut Shell) -> io::Result<i32> {
+    let text = match expand_word(shell, word) {
+        Ok(text) => text,
+        Err(err) => {
+            exit_if_fatal(&err, shell);
+            eprintln!("rsh: {err}");
+            return Ok(1);
+        }
+    };
+
+    let mut status = 0;
+    let mut falling_through = false;
+    for item in items {
+        if !falling_through {
+            let mut matched = false;
+            for pattern in &item.patterns {
+                match pattern_matches(shell, pattern, &text) {
+                    Ok(true) => {
+                        matched = true;
+                        break;
+                    }
+                    Ok(false) => {}
+                    Err(err) => {
+                        exit_if_fatal(&err, shell);
+                        eprintln!("rsh: {err}");
+                        return Ok(1);
+                    }
+                }
+            }
+            if !matched {
+                continue;
+            }
+        }
+
+        execute_list(&item.body, shell)?;
+        status = match item.body.items.is_empty() {
+            true => 0,
+            false => shell.last_status,
+        };
+        if shell.flow.is_some() {
+            break;
+        }
+
+        match item.terminator {
+            CaseTerminator::Break => break,
+            CaseTerminator::FallThrough => falling_through = true,
+            CaseTerminator::Continue => falling_through = false,
+        }
+    }
+
+    Ok(status)
+}
+
+/// Run a loop with `break` and `continue` allowed inside it
+fn in_loop(shell: &mut Shell, run: impl FnOnce(&mut Shell) -> io::Result<i32>) -> io::Result<i32> {
+    shell.loop_depth += 1;
+    let result = run(shell);
+    shell.loop_depth -= 1;
+    result
+}
+
+/// Act on a `break` or `continue` that reached the loop after a list ran, and report
+/// whether the loop has to end; flows meant for outer loops are passed on
+fn leaves_loop(shell: &mut Shell) -> bool {
+    match shell.flow.take() {
+        None | Some(Flow::Continue(1)) => false,
+        Some(Flow::Break(1)) => true,
+        Some(Flow::Break(count)) => {
+            shell.flow = Some(Flow::Break(count - 1));
+            true
+        }
+        Some(Flow::Continue(count)) => {
+            shell.flow = Some(Flow::Continue(count - 1));
+            true
+        }
+        Some(flow @ (Flow::Interrupt | Flow::Return)) => {
+            shell.flow = Some(flow);
+            true
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::io;
+
+use super::execute_compound;
+use crate::executor::run_trap;
+use crate::parser::ast::FunctionDefinition;
+use crate::shell::{Flow, Shell};
+use crate::traps::Trap;
+
+/// Deepest nesting of function calls, so that runaway recursion is reported instead of
+/// overflowing the stack
+const MAX_FUNCTION_DEPTH: usize = 1000;
+
+/// Call a shell function with `arguments` as its positional parameters and return its
+/// exit status
+pub fn call_function(
+    function: &FunctionDefinition,
+    arguments: &[String],
+    shell: &mut Shell,
+) -> io::Result<i32> {
+    if shell.function_depth >= MAX_FUNCTION_DEPTH {
+        eprintln!(
+            "rsh: {}: maximum function nesting level exceeded ({MAX_FUNCTION_DEPTH})",
+            function.name
+        );
+        return Ok(1);
+    }
+
+    let positional = std::mem::replace(&mut shell.positional, arguments.to_vec());
+    // Loops around the call cannot be left with `break` or `continue` from inside it
+    let loop_depth = std::mem::replace(&mut shell.loop_depth, 0);
+    let traps = shell.traps.suspend_for_function();
+    shell.variables.push_scope();
+    shell.function_depth += 1;
+
+    let result = execute_compound(&function.body, &function.redirections, shell);
+    let status = match shell.flow {
+        Some(Flow::Return) => {
+            shell.flow = None;
+            shell.last_status
+        }
+        _ => result.as_ref().copied().unwrap_or(1),
+    };
+
+    // The RETURN trap runs as part of the function, seeing its locals and parameters
+    shell.last_status = status;
+    let trapped = run_trap(shell, Trap::Return);
+
+    shell.function_depth -= 1;
+    shell.variables.pop_scope();
+    shell.traps.resume_after_function(traps);
+    shell.loop_depth = loop_depth;
+    shell.positional = positional;
+
+    result.and(trapped).map(|_| status)
+}
@@ -1,3 +1,7 @@
+mod compound;
+mod function;
 mod pipeline;
 
+pub use compound::execute_compound;
+pub use function::call_function;
 pub use pipeline::pipeline_handler;
@@ -1,153 +1,281 @@
-use crate::builtins::VALID_COMMANDS_BUILTIN;
-use pathsearch::find_executable_in_path;
-use shlex::split;
+use super::{call_function, execute_compound};
+use crate::builtins::{VALID_COMMANDS_BUILTIN, run_builtin};
+use crate::executor::{Group, assign_temporary, exit_if_fatal, fork_shell};
+use crate::expansion::expand_words;
+use crate::jobs::{Process, ProcessState, prepare_process, wait_foreground};
+use crate::parser::ast::{Command as AstCommand, Pipeline, SimpleCommand};
+use crate::redirection::Redirections;
+use crate::shell::Shell;
+use crate::utils::io_error_message;
 use std::{
-    env::current_dir,
-    io::{stdout, Write},
+    io::{self, PipeReader, PipeWriter},
+    os::{
+        fd::{AsRawFd, OwnedFd, RawFd},
+        unix::process::CommandExt,
+    },
     process::{Command, Stdio},
 };
 
+/// A pipeline stage once its words are expanded
+enum Stage {
+    /// An external command or a forked copy of the shell, already started
+    Process(i32),
+    /// A stage that could not run, with its exit status
+    Finished(i32),
+}
+
 /// Handle piped commands (e.g., "cmd1 | cmd2 | cmd3")
-/// Returns Ok(true) if pipeline was handled, Ok(false) if not a pipeline
-pub fn pipeline_handler(command: &str) -> std::io::Result<bool> {
-    let cmds = command.split(" | ").collect::<Vec<&str>>();
-    let mut last_output: Option<Stdio> = None;
-    let mut children = Vec::new();
+/// Returns Ok(Some(statuses)) with every command's status if the pipeline was handled,
+/// Ok(None) if not a pipeline
+pub fn pipeline_handler(
+    pipeline: &Pipeline,
+    shell: &mut Shell,
+) -> std::io::Result<Option<Vec<i32>>> {
+    let cmds = &pipeline.commands;
 
     if cmds.len() > 1 {
-        for (i, cmd) in cmds.iter().enumerate() {
-            let whole_command = split(cmd.trim()).unwrap_or([].to_vec());
-            let command = whole_command.first().unwrap();
-            let arguments = whole_command[1..].to_vec();
-
-            match command.trim() {
-                "exit" => std::process::exit(0),
-                "echo" => {
-                    let builtin_output = arguments.join(" ");
-                    let builtin_output = format!("{}\n", builtin_output.trim());
-                    let mut fake_process = Command::new("cat")
-                        .stdin(Stdio::piped())
-                        .stdout(if i == cmds.len() - 1 {
-                            Stdio::inherit()
-                        } else {
-                            Stdio::piped()
-                        })
-                        .spawn()?;
-
-                    if let Some(mut fake_stdin) = fake_process.stdin.take() {
-                        fake_stdin.write_all(builtin_output.as_bytes())?;
-                    }
+        // Every stage runs in a process of its own, builtins and functions included, so
+        // that none of them can change the shell's state or block on a full pipe
+        let mut stages = Vec::with_capacity(cmds.len());
+        let mut last_output: Option<PipeReader> = None;
+        // Process group of the pipeline's processes, led by the first of them
+        let mut pgid = None;
 
-                    last_output = fake_process.stdout.take().map(Stdio::from);
-                    children.push(fake_process);
+        for (i, cmd) in cmds.iter().enumerate() {
+            let (reader, writer) = match i == cmds.len() - 1 {
+                true => (None, None),
+                false => {
+                    let (reader, writer) = io::pipe()?;
+                    (Some(reader), Some(writer))
                 }
-                "type" => {
-                    if VALID_COMMANDS_BUILTIN.contains(&arguments.join(" ").trim()) {
-                        let builtin_output = arguments.join(" ");
-                        let builtin_output =
-                            format!("{} is a shell builtin\n", builtin_output.trim());
-
-                        let mut fake_process = Command::new("cat")
-                            .stdin(Stdio::piped())
-                            .stdout(if i == cmds.len() - 1 {
-                                Stdio::inherit()
-                            } else {
-                                Stdio::piped()
-                            })
-                            .spawn()?;
-
-                        if let Some(mut fake_stdin) = fake_process.stdin.take() {
-                            fake_stdin.write_all(builtin_output.as_bytes())?;
-                        }
-
-                        last_output = fake_process.stdout.take().map(Stdio::from);
-                        children.push(fake_process);
-                    } else if let Some(path) = find_executable_in_path(&arguments.join(" ").trim())
+            };
+            let stdin = last_output.take();
+            last_output = reader;
+
+            let simple = match cmd {
+                AstCommand::Simple(simple) => simple,
+                AstCommand::Compound(compound, redirections) => {
+                    let held = held_fds(last_output.as_ref());
+                    let run = |shell: &mut Shell| execute_compound(compound, redirections, shell);
+                    let stage = fork(run, stdin, writer, pgid, &held, shell)?;
+                    if let (Stage::Process(pid), None, Some(terminal)) =
+                        (&stage, pgid, shell.terminal)
                     {
-                        let builtin_output = arguments.join(" ");
-                        let builtin_output =
-                            format!("{} is {}\n", builtin_output.trim(), path.to_str().unwrap());
-
-                        let mut fake_process = Command::new("cat")
-                            .stdin(Stdio::piped())
-                            .stdout(if i == cmds.len() - 1 {
-                                Stdio::inherit()
-                            } else {
-                                Stdio::piped()
-                            })
-                            .spawn()?;
-
-                        if let Some(mut fake_stdin) = fake_process.stdin.take() {
-                            fake_stdin.write_all(builtin_output.as_bytes())?;
-                        }
-
-                        last_output = fake_process.stdout.take().map(Stdio::from);
-                        children.push(fake_process);
-                    } else {
-                        let builtin_output = arguments.join(" ");
-                        let builtin_output = format!("{}: not found\n", builtin_output.trim());
-
-                        let mut fake_process = Command::new("cat")
-                            .stdin(Stdio::piped())
-                            .stdout(if i == cmds.len() - 1 {
-                                Stdio::inherit()
-                            } else {
-                                Stdio::piped()
-                            })
-                            .spawn()?;
-
-                        if let Some(mut fake_stdin) = fake_process.stdin.take() {
-                            fake_stdin.write_all(builtin_output.as_bytes())?;
-                        }
-
-                        last_output = fake_process.stdout.take().map(Stdio::from);
-                        children.push(fake_process);
+                        terminal.give(*pid);
+                        pgid = Some(*pid);
                     }
+                    stages.push(stage);
+                    continue;
                 }
-                "pwd" => {
-                    let builtin_output = format!("{}\n", current_dir()?.to_str().unwrap());
-
-                    let mut fake_process = Command::new("cat")
-                        .stdin(Stdio::piped())
-                        .stdout(if i == cmds.len() - 1 {
-                            Stdio::inherit()
-                        } else {
-                            Stdio::piped()
-                        })
-                        .spawn()?;
-
-                    if let Some(mut fake_stdin) = fake_process.stdin.take() {
-                        fake_stdin.write_all(builtin_output.as_bytes())?;
-                    }
+                // Definitions in a pipeline only happen in a copy of the shell
+                AstCommand::Function(_) => {
+                    stages.push(Stage::Finished(0));
+                    continue;
+                }
+            };
 
-                    last_output = fake_process.stdout.take().map(Stdio::from);
-                    children.push(fake_process);
+            let words = match expand_words(shell, &simple.words) {
+                Ok(words) => words,
+                Err(err) => {
+                    exit_if_fatal(&err, shell);
+                    eprintln!("rsh: {err}");
+                    stages.push(Stage::Finished(1));
+                    continue;
                 }
-                _ => {
-                    let mut child_process = Command::new(command)
-                        .args(&arguments)
-                        .stdin(last_output.unwrap_or(Stdio::inherit()))
-                        .stdout(if i == cmds.len() - 1 {
-                            Stdio::inherit()
-                        } else {
-                            Stdio::piped()
-                        })
-                        .spawn()?;
-
-                    last_output = child_process.stdout.take().map(Stdio::from);
-
-                    children.push(child_process);
+            };
+            let Some(command) = words.first() else {
+                stages.push(Stage::Finished(0));
+                continue;
+            };
+
+            let command = command.trim();
+            if shell.functions.contains_key(command) || VALID_COMMANDS_BUILTIN.contains(&command) {
+                let held = held_fds(last_output.as_ref());
+                let run = |shell: &mut Shell| Ok(run_in_copy(simple, &words, shell));
+                let stage = fork(run, stdin, writer, pgid, &held, shell)?;
+                if let (Stage::Process(pid), None, Some(terminal)) = (&stage, pgid, shell.terminal)
+                {
+                    terminal.give(*pid);
+                    pgid = Some(*pid);
                 }
+                stages.push(stage);
+                continue;
+            }
+
+            let stage = spawn(simple, &words, stdin, writer, pgid, shell);
+            if let (Stage::Process(pid), None, Some(terminal)) = (&stage, pgid, shell.terminal) {
+                terminal.give(*pid);
+                pgid = Some(*pid);
             }
+            stages.push(stage);
+        }
+
+        let processes = stages
+            .iter()
+            .filter_map(|stage| match stage {
+                Stage::Process(pid) => Some(Process {
+                    pid: *pid,
+                    state: ProcessState::Running,
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut waited = match processes.is_empty() {
+            true => Vec::new(),
+            false => wait_foreground(shell, processes, pgid, pipeline.to_string())?,
+        }
+        .into_iter();
+
+        let statuses = stages
+            .into_iter()
+            .map(|stage| match stage {
+                Stage::Process(_) => waited.next().unwrap(),
+                Stage::Finished(status) => status,
+            })
+            .collect();
+
+        return Ok(Some(statuses));
+    }
+
+    Ok(None)
+}
+
+/// Start an external pipeline stage reading from `stdin` and writing to `stdout`,
+/// or the terminal where those are `None`
+fn spawn(
+    simple: &SimpleCommand,
+    words: &[String],
+    stdin: Option<PipeReader>,
+    stdout: Option<PipeWriter>,
+    pgid: Option<i32>,
+    shell: &mut Shell,
+) -> Stage {
+    let (command, arguments) = words.split_first().unwrap();
+
+    let saved_variables = match assign_temporary(&simple.assignments, shell) {
+        Ok(saved_variables) => saved_variables,
+        Err(err) => {
+            exit_if_fatal(&err, shell);
+            eprintln!("rsh: {err}");
+            return Stage::Finished(1);
         }
-        stdout().flush()?;
+    };
+    let redirections = match Redirections::open(&simple.redirections, shell) {
+        Ok(redirections) => redirections,
+        Err(err) => {
+            eprintln!("rsh: {err}");
+            shell.variables.restore(saved_variables);
+            return Stage::Finished(1);
+        }
+    };
+
+    if shell.find_executable(command.trim()).is_none() {
+        println!("{}: command not found", command.trim());
+        shell.variables.restore(saved_variables);
+        return Stage::Finished(127);
+    }
 
-        for mut child in children {
-            let _ = child.wait();
+    let mut child = Command::new(command);
+    child
+        .args(arguments)
+        .env_clear()
+        .envs(shell.variables.exported())
+        .stdin(stdin.map_or(Stdio::inherit(), Stdio::from))
+        .stdout(stdout.map_or(Stdio::inherit(), Stdio::from));
+    prepare_process(&mut child, shell.terminal, pgid, true);
+    // The stage's own redirections are applied on top of the pipe
+    unsafe {
+        child.pre_exec(move || redirections.apply_in_child());
+    }
+    let spawned = child.spawn();
+    shell.variables.restore(saved_variables);
+
+    match spawned {
+        Ok(child) => Stage::Process(child.id() as i32),
+        Err(err) => {
+            eprintln!("rsh: {command}: {}", io_error_message(&err));
+            Stage::Finished(126)
         }
+    }
+}
+
+/// Start a pipeline stage that runs inside the shell in a forked copy of it, reading
+/// from `stdin` and writing to `stdout`, or the terminal where those are `None`
+fn fork(
+    run: impl FnOnce(&mut Shell) -> io::Result<i32>,
+    stdin: Option<PipeReader>,
+    stdout: Option<PipeWriter>,
+    pgid: Option<i32>,
+    held: &[RawFd],
+    shell: &mut Shell,
+) -> io::Result<Stage> {
+    let pid = fork_shell(shell, Group::Job(pgid), held, |shell| {
+        let pipes =
+            Redirections::default().with_pipes(stdin.map(OwnedFd::from), stdout.map(OwnedFd::from));
+        if let Err(err) = pipes.apply_in_child() {
+            eprintln!("rsh: {}", io_error_message(&err));
+            return Ok(1);
+        }
+        drop(pipes);
+        run(shell)
+    })?;
+
+    Ok(Stage::Process(pid))
+}
 
-        return Ok(true);
+/// Pipe end the shell still holds for the next stage, which must not stay open in a
+/// forked stage, or the next stage would never see end of file
+fn held_fds(last_output: Option<&PipeReader>) -> Vec<RawFd> {
+    last_output.map(AsRawFd::as_raw_fd).into_iter().collect()
+}
+
+/// Run a builtin or call a shell function as a pipeline stage, inside a forked copy of
+/// the shell so that it cannot change the shell's own state
+fn run_in_copy(simple: &SimpleCommand, words: &[String], shell: &mut Shell) -> i32 {
+    let (command, arguments) = words.split_first().unwrap();
+
+    if let Err(err) = assign_temporary(&simple.assignments, shell) {
+        exit_if_fatal(&err, shell);
+        eprintln!("rsh: {err}");
+        return 1;
     }
+    let redirected = Redirections::open(&simple.redirections, shell).and_then(Redirections::apply);
+    let _saved_fds = match redirected {
+        Ok(saved_fds) => saved_fds,
+        Err(err) => {
+            eprintln!("rsh: {err}");
+            return 1;
+        }
+    };
+
+    let status = match shell.functions.get(command.trim()).cloned() {
+        Some(function) => call_function(&function, arguments, shell),
+        None => Ok(run_builtin(shell, command.trim(), arguments).unwrap_or(127)),
+    };
+    status.unwrap_or_else(|err| {
+        eprintln!("rsh: {err}");
+        1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::execute_list;
+    use crate::parser::parse;
+
+    #[test]
+    fn builtin_stages_leave_the_shell_unchanged() -> io::Result<()> {
+        let mut shell = Shell::new(Vec::new());
+        let cwd = std::env::current_dir()?;
 
-    Ok(false)
+        let list = parse("cd / | true; export PIPED=1 | true; exit 3 | true").unwrap();
+        execute_list(&list, &mut shell)?;
+
+        assert_eq!(std::env::current_dir()?, cwd);
+        assert_eq!(shell.variables.get("PIPED"), None);
+        assert_eq!(shell.pipestatus, [3, 0]);
+        assert_eq!(shell.last_status, 0);
+        Ok(())
+    }
 }
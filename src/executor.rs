@@ -1,28 +1,29 @@
-use pathsearch::find_executable_in_path;
-use shlex::split;
 use std::{
-    env::{self, current_dir, set_current_dir},
-    fs::OpenOptions,
-    io::{stderr, stdout, Write},
-    path::Path,
-    process::Command,
+    fs::File,
+    io::{Read, Write, stderr, stdout},
+    os::{
+        fd::{FromRawFd, RawFd},
+        unix::process::ExitStatusExt,
+    },
+    process::{Command, ExitStatus},
+    rc::Rc,
 };
 
-use crate::builtins::VALID_COMMANDS_BUILTIN;
-use crate::commands::pipeline_handler;
-use crate::history::get_history;
-use crate::redirection::RedirectionKind;
-
-/// Redirections with their operators and kinds (runtime version)
-const REDIRECTIONS: [(&[&str], RedirectionKind); 4] = [
-    (&[">", "1>"], RedirectionKind::Stdout),
-    (&["2>"], RedirectionKind::Stderr),
-    (&[">>", "1>>"], RedirectionKind::AppendStdout),
-    (&["2>>"], RedirectionKind::AppendStderr),
-];
+use crate::builtins::run_builtin;
+use crate::commands::{call_function, execute_compound, pipeline_handler};
+use crate::expansion::{ExpansionError, expand_assignment, expand_words};
+use crate::jobs::{Jobs, Process, ProcessState, job_line, prepare_process, wait_foreground};
+use crate::parser::ast::{self, AndOr, AndOrOperator, Assignment, CompoundCommand, List, Pipeline};
+use crate::parser::parse;
+use crate::redirection::Redirections;
+use crate::shell::Shell;
+use crate::signals;
+use crate::traps::Trap;
+use crate::utils::{exit_code, io_error_message};
+use crate::variables::Variable;
 
 /// Execute a shell command
-pub fn run_sh(command: &mut String, local_history: &mut Vec<String>) -> std::io::Result<()> {
+pub fn run_sh(command: &mut String, shell: &mut Shell) -> std::io::Result<()> {
     if command.is_empty() {
         println!();
         print!("\r$ ");
@@ -31,227 +32,457 @@ pub fn run_sh(command: &mut String, local_history: &mut Vec<String>) -> std::io:
     println!();
     stdout().flush()?;
 
-    if pipeline_handler(command)? {
-        command.clear();
-        return Ok(());
+    let parsed = parse(command);
+    command.clear();
+
+    match parsed {
+        Ok(list) => run_list(&list, shell),
+        Err(err) => {
+            eprintln!("rsh: {err}");
+            shell.last_status = 2;
+            Ok(())
+        }
     }
+}
 
-    let whole_command = split(command.trim()).unwrap_or([].to_vec());
-    command.clear();
-    let command = whole_command.first().unwrap();
-    let arguments = whole_command[1..].to_vec();
-
-    let mut redir_kind = &RedirectionKind::Stdout;
-    let mut to_file = "";
-    let mut from_content: Vec<String> = vec![];
-
-    for (ops, kind) in &REDIRECTIONS {
-        let mut argument_iter = arguments.iter();
-        if let Some(redirect_pos) = argument_iter.position(|s| ops.contains(&s.as_str())) {
-            if let Some(file) = argument_iter.next() {
-                redir_kind = kind;
-                to_file = file;
-                from_content = arguments[..redirect_pos].to_vec();
-            }
+/// Execute a complete command line once it is parsed, then report finished background
+/// jobs and run the traps of signals caught meanwhile
+pub fn run_list(list: &List, shell: &mut Shell) -> std::io::Result<()> {
+    let result = execute_list(list, shell);
+
+    // An interrupt only abandons the rest of the command line it happened in
+    shell.flow = None;
+
+    // Background jobs that finished in the meantime are reported before the next prompt
+    report_finished_jobs(shell);
+
+    result.and_then(|_| run_pending_traps(shell))
+}
+
+/// Execute each item of a parsed command list in turn, stopping early for `break`,
+/// `continue` or an interrupt
+pub(crate) fn execute_list(list: &List, shell: &mut Shell) -> std::io::Result<()> {
+    for item in &list.items {
+        if shell.flow.is_some() {
+            break;
+        }
+
+        if item.background {
+            run_in_background(&item.and_or, shell)?;
+            continue;
         }
+
+        execute_and_or(&item.and_or, shell)?;
+        run_pending_traps(shell)?;
+    }
+
+    Ok(())
+}
+
+/// Run the action set for a trap, if any, leaving `$?` as it was before
+pub fn run_trap(shell: &mut Shell, trap: Trap) -> std::io::Result<()> {
+    let Some(action) = shell.traps.get(trap).map(str::to_string) else {
+        return Ok(());
+    };
+    // Commands run by a trap action do not trigger the command traps again
+    if action.is_empty() || (shell.traps.running && trap >= Trap::Debug) {
+        return Ok(());
+    }
+
+    run_trap_action(&action, shell)
+}
+
+/// Run the traps of signals caught since the last safe point between commands
+pub fn run_pending_traps(shell: &mut Shell) -> std::io::Result<()> {
+    for signal in signals::take_pending() {
+        run_trap(shell, Trap::Signal(signal))?;
+    }
+
+    Ok(())
+}
+
+/// Run the EXIT trap before the shell or a forked copy of it exits; it runs only once
+pub fn run_exit_trap(shell: &mut Shell) {
+    if let Some(action) = shell.traps.take(Trap::Exit)
+        && let Err(err) = run_trap_action(&action, shell)
+    {
+        eprintln!("rsh: {err}");
+    }
+}
+
+/// Exit a shell running a script or `-c` command on an expansion error meant to stop it,
+/// such as a failed `${name:?message}`, after reporting it and running the EXIT trap
+pub(crate) fn exit_if_fatal(err: &ExpansionError, shell: &mut Shell) {
+    if shell.interactive || !err.is_fatal() {
+        return;
     }
-    match command.trim() {
-        "exit" => {
-            let file_path = std::env::var_os("HISTFILE");
-            let existing_history_len = get_history().len();
-
-            if let Some(file_path) = file_path {
-                let mut file = OpenOptions::new()
-                    .append(true)
-                    .create(true)
-                    .write(true)
-                    .open(file_path)?;
-
-                file.write_all(local_history[existing_history_len..].join("\n").as_bytes())?;
-                file.write_all("\n".as_bytes())?;
-            }
 
-            std::process::exit(0);
+    eprintln!("rsh: {err}");
+    shell.last_status = match err {
+        ExpansionError::Unset(..) => 127,
+        _ => 1,
+    };
+    run_exit_trap(shell);
+    let _ = stdout().flush();
+    std::process::exit(shell.last_status);
+}
+
+fn run_trap_action(action: &str, shell: &mut Shell) -> std::io::Result<()> {
+    let list = match parse(action) {
+        Ok(list) => list,
+        Err(err) => {
+            eprintln!("rsh: {err}");
+            return Ok(());
         }
-        "echo" => {
-            if to_file.is_empty() {
-                println!("{}", arguments.join(" ").trim());
-            } else {
-                let is_append = matches!(
-                    redir_kind,
-                    RedirectionKind::AppendStdout | RedirectionKind::AppendStderr
-                );
-                let mut file = OpenOptions::new()
-                    .create(true)
-                    .append(is_append)
-                    .truncate(!is_append)
-                    .write(true)
-                    .open(to_file)?;
-
-                let from_content = from_content.join(" ");
-
-                match redir_kind {
-                    RedirectionKind::Stdout | RedirectionKind::AppendStdout => {
-                        file.write_all(from_content.as_bytes())?;
-                        file.write_all("\n".as_bytes())?;
-                    }
-                    RedirectionKind::Stderr | RedirectionKind::AppendStderr => {
-                        file.write_all("".as_bytes())?;
-                        println!("{}", from_content.trim());
-                    }
+    };
+
+    let status = shell.last_status;
+    let running = std::mem::replace(&mut shell.traps.running, true);
+    let result = execute_list(&list, shell);
+    shell.traps.running = running;
+    shell.last_status = status;
+
+    result
+}
+
+/// Process group a forked copy of the shell runs in, when the shell has job control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Group {
+    /// The shell's own group, for a copy the shell waits on as part of a command
+    Shell,
+    /// The group of a job, which the copy leads when given `None`
+    Job(Option<i32>),
+}
+
+/// Run `run` in a forked copy of the shell and return the copy's process ID. The copy
+/// closes the file descriptors in `held`, forgets the parent's jobs and traps, and exits
+/// with the status `run` returns once its EXIT trap has run. A copy running as a job
+/// stops with its commands on Ctrl-Z, so that the job as a whole is stopped
+pub(crate) fn fork_shell(
+    shell: &mut Shell,
+    group: Group,
+    held: &[RawFd],
+    run: impl FnOnce(&mut Shell) -> std::io::Result<i32>,
+) -> std::io::Result<i32> {
+    // Anything still buffered would otherwise be written by both processes
+    stdout().flush()?;
+    stderr().flush()?;
+
+    match unsafe { libc::fork() } {
+        -1 => Err(std::io::Error::last_os_error()),
+        0 => {
+            for &fd in held {
+                unsafe { libc::close(fd) };
+            }
+            // Commands the copy runs stay in its group, so it gives up the terminal
+            match (group, shell.terminal.take()) {
+                (Group::Job(pgid), Some(_)) => {
+                    unsafe { libc::setpgid(0, pgid.unwrap_or(0)) };
+                    signals::restore_defaults();
                 }
+                _ => signals::restore_interrupts(),
             }
+            shell.subshell = true;
+            shell.jobs = Jobs::default();
+            shell.traps.clear_for_subshell();
+
+            shell.last_status = run(shell).unwrap_or_else(|err| {
+                eprintln!("rsh: {err}");
+                1
+            });
+            run_exit_trap(shell);
+            let _ = stdout().flush();
+            unsafe { libc::_exit(shell.last_status) }
         }
-        "type" => {
-            if VALID_COMMANDS_BUILTIN.contains(&arguments.join(" ").trim()) {
-                println!("{} is a shell builtin", arguments.join(" ").trim());
-            } else if let Some(path) = find_executable_in_path(&arguments.join(" ").trim()) {
-                println!(
-                    "{} is {}",
-                    &arguments.join(" ").trim(),
-                    path.to_str().unwrap()
-                );
-            } else {
-                println!("{}: not found", arguments.join(" ").trim());
+        pid => {
+            // Set in both processes, so it has happened whichever runs first
+            if let (Group::Job(pgid), Some(_)) = (group, shell.terminal) {
+                unsafe { libc::setpgid(pid, pgid.unwrap_or(pid)) };
             }
+            Ok(pid)
+        }
+    }
+}
+
+/// Run an AND-OR list in a forked copy of the shell without waiting for it, as a new job
+fn run_in_background(and_or: &AndOr, shell: &mut Shell) -> std::io::Result<()> {
+    let pid = fork_shell(shell, Group::Job(None), &[], |shell| {
+        execute_and_or(and_or, shell)?;
+        Ok(shell.last_status)
+    })?;
+
+    let process = Process {
+        pid,
+        state: ProcessState::Running,
+    };
+    let pgid = shell.terminal.map(|_| pid);
+    let id = shell.jobs.add(vec![process], pgid, and_or.to_string());
+    if shell.interactive {
+        eprintln!("[{id}] {pid}");
+    }
+
+    shell.last_background = Some(pid);
+    shell.last_status = 0;
+    Ok(())
+}
+
+/// Print a notice for each job that has finished since the last check, and forget it;
+/// only an interactive shell prints the notices
+fn report_finished_jobs(shell: &mut Shell) {
+    if let Err(err) = shell.jobs.update() {
+        eprintln!("rsh: {err}");
+    }
+
+    let finished = shell.jobs.take_finished();
+    if shell.interactive {
+        for (marker, job) in finished {
+            eprintln!("{}", job_line(&job, marker, false));
         }
-        "pwd" => {
-            println!("{}", current_dir()?.to_str().unwrap());
+    }
+}
+
+/// Execute pipelines joined by `&&` and `||`, short-circuiting on the previous status
+fn execute_and_or(and_or: &AndOr, shell: &mut Shell) -> std::io::Result<()> {
+    shell.last_status = execute_pipeline(&and_or.first, shell)?;
+    let mut last_run = &and_or.first;
+
+    for (op, pipeline) in &and_or.rest {
+        let should_run = match op {
+            AndOrOperator::And => shell.last_status == 0,
+            AndOrOperator::Or => shell.last_status != 0,
+        };
+
+        if should_run && shell.flow.is_none() {
+            shell.last_status = execute_pipeline(pipeline, shell)?;
+            last_run = pipeline;
         }
-        "history" => {
-            let mut history_size: usize = local_history.len();
-            let mut skip_print = false;
-
-            if !arguments.is_empty() {
-                let arg = &arguments[0];
-
-                if arg == "-r" && arguments.len() > 1 {
-                    let file = &arguments[1];
-                    let file_content = std::fs::read_to_string(file)?;
-
-                    let file_content = file_content.lines().collect::<Vec<&str>>();
-                    local_history.extend(file_content.iter().map(ToString::to_string));
-
-                    skip_print = true;
-                } else if arg == "-w" && arguments.len() > 1 {
-                    let file = &arguments[1];
-                    let mut file = OpenOptions::new().create(true).write(true).open(file)?;
-
-                    file.write_all(local_history.join("\n").as_bytes())?;
-                    file.write_all("\n".as_bytes())?;
-
-                    skip_print = true;
-                } else if arg == "-a" && arguments.len() > 1 {
-                    let file_name = &arguments[1];
-                    let mut file = OpenOptions::new()
-                        .create(true)
-                        .write(true)
-                        .append(true)
-                        .open(file_name)?;
-
-                    let search_str = format!("history -a {}", file_name);
-
-                    let history_slice = local_history
-                        .iter()
-                        .rev()
-                        .enumerate()
-                        .filter(|&(_, s)| *s == search_str)
-                        .take(2)
-                        .map(|(i, _)| local_history.len() - i)
-                        .collect::<Vec<usize>>();
-
-                    //as it is reversed, we need to slice reversed, if it contains one occurrence
-                    //then it happened once
-
-                    if history_slice.len() > 1 {
-                        file.write_all(
-                            local_history[history_slice[1]..history_slice[0]]
-                                .join("\n")
-                                .as_bytes(),
-                        )?;
-                        file.write_all("\n".as_bytes())?;
-                    } else {
-                        file.write_all(local_history[..].join("\n").as_bytes())?;
-                        file.write_all("\n".as_bytes())?;
-                    }
-                    skip_print = true;
-                }
+    }
 
-                history_size = arg.parse::<usize>().unwrap_or(local_history.len());
-            }
+    // Only a failure of the final pipeline counts, as the others were tested by `&&`
+    // or `||`, and a negated pipeline's failure is what it asked for; conditions of
+    // `if`, `while` and `until` are tested too. A compound command's failure was
+    // already reported by the command inside it
+    let last = and_or
+        .rest
+        .last()
+        .map_or(&and_or.first, |(_, pipeline)| pipeline);
+    let tested = shell.in_condition || !std::ptr::eq(last_run, last) || last.negated;
+    if shell.last_status != 0 && !tested && !is_compound(last) {
+        run_trap(shell, Trap::Err)?;
+    }
 
-            let history_skip = if history_size > local_history.len() {
-                0
-            } else {
-                local_history.len() - history_size
-            };
+    Ok(())
+}
 
-            if !skip_print {
-                for (i, cmd) in local_history.iter().enumerate().skip(history_skip) {
-                    println!("    {} {}", i + 1, cmd);
-                }
-            }
+/// Execute a pipeline, recording the status of each command, and return its exit status
+fn execute_pipeline(pipeline: &Pipeline, shell: &mut Shell) -> std::io::Result<i32> {
+    if shell.traps.get(Trap::Debug).is_some() && !shell.traps.running && !is_compound(pipeline) {
+        // The action can see the command about to run, as in bash
+        let _ = shell.variables.set("BASH_COMMAND", pipeline.to_string());
+        run_trap(shell, Trap::Debug)?;
+    }
+
+    let statuses = match pipeline_handler(pipeline, shell)? {
+        Some(statuses) => statuses,
+        None => vec![execute_command(&pipeline.commands[0], shell)?],
+    };
+
+    let status = if shell.options.pipefail {
+        statuses
+            .iter()
+            .rev()
+            .copied()
+            .find(|&s| s != 0)
+            .unwrap_or(0)
+    } else {
+        *statuses.last().unwrap()
+    };
+    shell.pipestatus = statuses;
+
+    Ok(match (pipeline.negated, status) {
+        (false, status) => status,
+        (true, 0) => 1,
+        (true, _) => 0,
+    })
+}
+
+/// Whether a pipeline is a lone compound command other than an arithmetic one, which
+/// the DEBUG and ERR traps leave to the commands inside it
+fn is_compound(pipeline: &Pipeline) -> bool {
+    matches!(
+        pipeline.commands.as_slice(),
+        [ast::Command::Compound(compound, _)] if !matches!(compound, CompoundCommand::Arithmetic(_))
+    )
+}
+
+/// Execute a single command outside of a pipeline and return its exit status
+fn execute_command(command: &ast::Command, shell: &mut Shell) -> std::io::Result<i32> {
+    let simple = match command {
+        ast::Command::Simple(simple) => simple,
+        ast::Command::Compound(compound, redirections) => {
+            return execute_compound(compound, redirections, shell);
         }
-        "." => {
-            set_current_dir(current_dir()?)?;
+        ast::Command::Function(function) => {
+            let function = Rc::new(function.clone());
+            shell.functions.insert(function.name.clone(), function);
+            return Ok(0);
         }
-        ".." => {
-            let new_dir = current_dir()?.pop().to_string();
-            set_current_dir(new_dir)?;
+    };
+
+    shell.substitution_status = None;
+    let whole_command = match expand_words(shell, &simple.words) {
+        Ok(whole_command) => whole_command,
+        Err(err) => {
+            exit_if_fatal(&err, shell);
+            eprintln!("rsh: {err}");
+            return Ok(1);
         }
-        "cd" => {
-            let new_arg = &arguments[0].replace("~", env::home_dir().unwrap().to_str().unwrap());
-            let new_dir = Path::new(new_arg).to_path_buf();
-
-            match set_current_dir(new_dir) {
-                Ok(_) => {}
-                Err(_) => {
-                    println!("cd: {}: No such file or directory", new_arg)
-                }
-            }
+    };
+    let Some((command, arguments)) = whole_command.split_first() else {
+        // Redirections without a command still create or truncate their files
+        if let Err(err) = Redirections::open(&simple.redirections, shell) {
+            eprintln!("rsh: {err}");
+            return Ok(1);
         }
-        _ => match find_executable_in_path(command.trim()) {
+        return Ok(assign_variables(&simple.assignments, shell));
+    };
+    let saved_variables = match assign_temporary(&simple.assignments, shell) {
+        Ok(saved_variables) => saved_variables,
+        Err(err) => {
+            exit_if_fatal(&err, shell);
+            eprintln!("rsh: {err}");
+            return Ok(1);
+        }
+    };
+
+    // Builtins write through the shell's own descriptors, so redirect those for the
+    // duration of the command; external commands inherit them
+    let redirected = Redirections::open(&simple.redirections, shell).and_then(Redirections::apply);
+    let _saved_fds = match redirected {
+        Ok(saved_fds) => saved_fds,
+        Err(err) => {
+            eprintln!("rsh: {err}");
+            shell.variables.restore(saved_variables);
+            return Ok(1);
+        }
+    };
+
+    // Functions come before builtins and commands of the same name
+    if let Some(function) = shell.functions.get(command.trim()).cloned() {
+        let status = call_function(&function, arguments, shell);
+        shell.variables.restore(saved_variables);
+        return status;
+    }
+
+    let status = match run_builtin(shell, command.trim(), arguments) {
+        Some(status) => status,
+        None => match shell.find_executable(command.trim()) {
             Some(_) => {
-                let out = Command::new(command)
-                    .args(if !to_file.is_empty() {
-                        &from_content
-                    } else {
-                        &arguments
-                    })
-                    .output()?;
-
-                if to_file.is_empty() {
-                    stdout().write_all(&out.stdout)?;
-                    if out.status.success() && out.stdout.last() != Some(&b'\n') {
-                        println!();
+                // Standard streams are inherited, so output appears as it is written and
+                // interactive programs can use the terminal
+                let mut child = Command::new(command);
+                child
+                    .args(arguments)
+                    .env_clear()
+                    .envs(shell.variables.exported());
+                prepare_process(&mut child, shell.terminal, None, true);
+
+                match child.spawn() {
+                    Ok(child) => {
+                        let pid = child.id() as i32;
+                        let pgid = shell.terminal.map(|terminal| {
+                            terminal.give(pid);
+                            pid
+                        });
+                        let process = Process {
+                            pid,
+                            state: ProcessState::Running,
+                        };
+                        wait_foreground(shell, vec![process], pgid, simple.to_string())?[0]
                     }
-                    stderr().write_all(&out.stderr)?;
-                } else {
-                    let is_append = matches!(
-                        redir_kind,
-                        RedirectionKind::AppendStdout | RedirectionKind::AppendStderr
-                    );
-                    let mut file = OpenOptions::new()
-                        .create(true)
-                        .append(is_append)
-                        .truncate(!is_append)
-                        .write(true)
-                        .open(to_file)?;
-
-                    match redir_kind {
-                        RedirectionKind::Stdout | RedirectionKind::AppendStdout => {
-                            file.write_all(&out.stdout)?;
-                            stderr().write_all(&out.stderr)?;
-                        }
-                        RedirectionKind::Stderr | RedirectionKind::AppendStderr => {
-                            file.write_all(&out.stderr)?;
-                            stdout().write_all(&out.stdout)?;
-                        }
+                    Err(err) => {
+                        eprintln!("rsh: {command}: {}", io_error_message(&err));
+                        126
                     }
                 }
             }
-            _ => println!("{}: command not found", &command.trim()),
+            _ => {
+                println!("{}: command not found", &command.trim());
+                127
+            }
         },
+    };
+    shell.variables.restore(saved_variables);
+
+    Ok(status)
+}
+
+/// Apply `NAME=value` assignments that make up a whole command to the shell's variables
+fn assign_variables(assignments: &[Assignment], shell: &mut Shell) -> i32 {
+    for assignment in assignments {
+        let assigned = expand_assignment(shell, &assignment.value)
+            .and_then(|value| Ok(shell.variables.set(&assignment.name, value)?));
+
+        if let Err(err) = assigned {
+            exit_if_fatal(&err, shell);
+            eprintln!("rsh: {err}");
+            return 1;
+        }
     }
-    Ok(())
+
+    // A bare assignment takes the status of its last command substitution
+    shell.substitution_status.unwrap_or(0)
+}
+
+/// Run a command list in a forked copy of the shell, returning what it wrote to
+/// standard output together with its exit status
+pub fn capture_output(list: &List, shell: &mut Shell) -> std::io::Result<(Vec<u8>, i32)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = fds;
+
+    let forked = fork_shell(shell, Group::Shell, &[read_fd], |shell| {
+        unsafe {
+            libc::dup2(write_fd, libc::STDOUT_FILENO);
+            libc::close(write_fd);
+        }
+        execute_list(list, shell)?;
+        Ok(shell.last_status)
+    });
+    unsafe { libc::close(write_fd) };
+    let mut reader = unsafe { File::from_raw_fd(read_fd) };
+    let pid = forked?;
+
+    let mut output = Vec::new();
+    let read = reader.read_to_end(&mut output);
+
+    let mut status = 0;
+    while unsafe { libc::waitpid(pid, &mut status, 0) } == -1 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    read?;
+
+    Ok((output, exit_code(ExitStatus::from_raw(status))))
+}
+
+/// Apply `NAME=value` assignments preceding a command to its environment only,
+/// returning the variables to restore once the command has finished
+pub(crate) fn assign_temporary(
+    assignments: &[Assignment],
+    shell: &mut Shell,
+) -> Result<Vec<(String, Option<Variable>)>, ExpansionError> {
+    let mut expanded = Vec::with_capacity(assignments.len());
+    for assignment in assignments {
+        expanded.push((
+            assignment.name.clone(),
+            expand_assignment(shell, &assignment.value)?,
+        ));
+    }
+
+    Ok(shell.variables.assign_temporary(expanded)?)
 }
@@ -0,0 +1,222 @@
+/// Expand `{a,b}` lists and `{x..y[..step]}` sequences in the source text of a word,
+/// leaving quoted text and the insides of `${...}`, `$(...)` and backquotes alone
+pub fn expand_braces(raw: &str) -> Vec<String> {
+    let chars = raw.chars().collect::<Vec<_>>();
+    let mut search_from = 0;
+
+    while let Some(open) = find_unquoted(&chars, search_from, '{') {
+        search_from = open + 1;
+        let Some((close, commas)) = find_close(&chars, open) else {
+            continue;
+        };
+
+        let body = chars[open + 1..close].iter().collect::<String>();
+        let alternatives = if commas.is_empty() {
+            match sequence(&body) {
+                Some(items) => items,
+                None => continue,
+            }
+        } else {
+            let mut bounds = vec![open];
+            bounds.extend(&commas);
+            bounds.push(close);
+            bounds
+                .windows(2)
+                .flat_map(|pair| {
+                    expand_braces(&chars[pair[0] + 1..pair[1]].iter().collect::<String>())
+                })
+                .collect()
+        };
+
+        let preamble = &chars[..open].iter().collect::<String>();
+        let postscripts = expand_braces(&chars[close + 1..].iter().collect::<String>());
+
+        return alternatives
+            .iter()
+            .flat_map(|alternative| {
+                postscripts
+                    .iter()
+                    .map(move |postscript| format!("{preamble}{alternative}{postscript}"))
+            })
+            .collect();
+    }
+
+    vec![raw.to_string()]
+}
+
+/// Index just past a quoted string, substitution or escape starting at `i`, if there is one there
+fn skip_quoted(chars: &[char], i: usize) -> Option<usize> {
+    let skip_to = |from: usize, end: char| {
+        let mut j = from;
+        while j < chars.len() && chars[j] != end {
+            j += if chars[j] == '\\' && end != '\'' {
+                2
+            } else {
+                1
+            };
+        }
+        j + 1
+    };
+
+    match chars[i] {
+        '\\' => Some(i + 2),
+        '\'' => Some(skip_to(i + 1, '\'')),
+        '"' => Some(skip_to(i + 1, '"')),
+        '`' => Some(skip_to(i + 1, '`')),
+        '$' if matches!(chars.get(i + 1), Some('{' | '(')) => {
+            let (open, close) = if chars[i + 1] == '{' {
+                ('{', '}')
+            } else {
+                ('(', ')')
+            };
+            let mut depth = 0;
+            let mut j = i + 1;
+            while j < chars.len() {
+                if let Some(next) = skip_quoted(chars, j) {
+                    j = next;
+                    continue;
+                }
+                if chars[j] == open {
+                    depth += 1;
+                } else if chars[j] == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(j + 1);
+                    }
+                }
+                j += 1;
+            }
+            Some(j)
+        }
+        _ => None,
+    }
+}
+
+/// Position of the first unquoted `target` at or after `from`
+fn find_unquoted(chars: &[char], from: usize, target: char) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if let Some(next) = skip_quoted(chars, i) {
+            i = next;
+        } else if chars[i] == target {
+            return Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// The `}` matching the `{` at `open`, with the positions of the commas directly inside it
+fn find_close(chars: &[char], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = open;
+
+    while i < chars.len() {
+        if let Some(next) = skip_quoted(chars, i) {
+            i = next;
+            continue;
+        }
+
+        match chars[i] {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((i, commas));
+                }
+            }
+            ',' if depth == 1 => commas.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+/// Items of a `x..y` or `x..y..step` sequence of integers or single characters
+fn sequence(body: &str) -> Option<Vec<String>> {
+    let parts = body.split("..").collect::<Vec<_>>();
+    let (start, end, step) = match parts[..] {
+        [start, end] => (start, end, None),
+        [start, end, step] => (start, end, Some(step.parse::<i64>().ok()?)),
+        _ => return None,
+    };
+    let step = step.map_or(1, i64::unsigned_abs).max(1);
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = |text: &str| {
+            let digits = text.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = if padded(start) || padded(end) {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+
+        return Some(
+            range(first, last, step)
+                .map(|n| format!("{n:0width$}"))
+                .collect(),
+        );
+    }
+
+    let (mut start_chars, mut end_chars) = (start.chars(), end.chars());
+    match (
+        start_chars.next(),
+        start_chars.next(),
+        end_chars.next(),
+        end_chars.next(),
+    ) {
+        (Some(first), None, Some(last), None) if first.is_ascii() && last.is_ascii() => Some(
+            range(first as i64, last as i64, step)
+                .map(|c| char::from(c as u8).to_string())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Values from `first` to `last` inclusive, counting up or down by `step`
+fn range(first: i64, last: i64, step: u64) -> impl Iterator<Item = i64> {
+    let count = first.abs_diff(last) / step + 1;
+    let step = if first <= last {
+        step as i64
+    } else {
+        -(step as i64)
+    };
+    (0..count as i64).map(move |i| first + i * step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists() {
+        assert_eq!(expand_braces("src/{bin,lib}"), ["src/bin", "src/lib"]);
+        assert_eq!(expand_braces("file{,.bak}"), ["file", "file.bak"]);
+        assert_eq!(expand_braces("{a,b{1,2}}x"), ["ax", "b1x", "b2x"]);
+        assert_eq!(expand_braces("{a,b}{c,d}"), ["ac", "ad", "bc", "bd"]);
+    }
+
+    #[test]
+    fn sequences() {
+        assert_eq!(expand_braces("{1..4}"), ["1", "2", "3", "4"]);
+        assert_eq!(expand_braces("{10..1..4}"), ["10", "6", "2"]);
+        assert_eq!(expand_braces("{08..11}"), ["08", "09", "10", "11"]);
+        assert_eq!(expand_braces("{a..e..2}"), ["a", "c", "e"]);
+    }
+
+    #[test]
+    fn literal_braces() {
+        assert_eq!(expand_braces("{}"), ["{}"]);
+        assert_eq!(expand_braces("{a}"), ["{a}"]);
+        assert_eq!(expand_braces("'{a,b}'"), ["'{a,b}'"]);
+        assert_eq!(expand_braces("${x:-{a,b}}"), ["${x:-{a,b}}"]);
+        assert_eq!(expand_braces("{x}{1..2}"), ["{x}1", "{x}2"]);
+    }
+}
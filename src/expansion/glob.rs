@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+
+use super::pattern::Pattern;
+use super::{ExpansionError, Field, Origin};
+use crate::shell::Shell;
+
+/// How one `/`-separated component of a pathname pattern is matched
+enum Component {
+    Literal(String),
+    Pattern(Pattern),
+    /// `**` with `globstar` enabled: any number of directories
+    Globstar,
+}
+
+/// Replace a field containing unquoted pattern characters with the sorted paths it matches
+pub fn expand_pathnames(shell: &Shell, field: Field) -> Result<Vec<String>, ExpansionError> {
+    let components = field
+        .chars
+        .split(|&(c, _)| c == '/')
+        .map(|chars| component(shell, chars))
+        .collect::<Vec<_>>();
+
+    let literal = components
+        .iter()
+        .all(|component| matches!(component, Component::Literal(_)));
+    if shell.options.noglob || literal {
+        return Ok(vec![field.text()]);
+    }
+
+    let absolute = field.chars.first().is_some_and(|&(c, _)| c == '/');
+    let mut paths = vec![if absolute { "/" } else { "" }.to_string()];
+    let last = components.len() - 1;
+
+    for (i, component) in components.iter().enumerate() {
+        if i == 0 && absolute {
+            continue;
+        }
+
+        paths = match component {
+            Component::Literal(name) => paths.iter().map(|path| join(path, name)).collect(),
+            Component::Pattern(pattern) => paths
+                .iter()
+                .flat_map(|path| matching_entries(shell, path, pattern, i < last))
+                .collect(),
+            Component::Globstar => paths
+                .iter()
+                .flat_map(|path| {
+                    // Zero directories leave the path as it is; at the end only a directory
+                    // that was named explicitly is included, with a trailing slash
+                    let mut found = Vec::new();
+                    if i < last {
+                        found.push(path.clone());
+                    } else if !path.is_empty() {
+                        found.push(join(path, ""));
+                    }
+                    descendants(shell, path, i < last, &mut found);
+                    found
+                })
+                .collect(),
+        };
+    }
+
+    paths.retain(|path| !path.is_empty() && fs::symlink_metadata(path).is_ok());
+    if !paths.is_empty() {
+        paths.sort();
+        paths.dedup();
+        return Ok(paths);
+    }
+
+    if shell.shopt.failglob {
+        Err(ExpansionError::NoMatch(field.text()))
+    } else if shell.shopt.nullglob {
+        Ok(Vec::new())
+    } else {
+        Ok(vec![field.text()])
+    }
+}
+
+fn component(shell: &Shell, chars: &[(char, Origin)]) -> Component {
+    if shell.shopt.globstar && chars == [('*', Origin::Literal), ('*', Origin::Literal)] {
+        return Component::Globstar;
+    }
+
+    let pattern = Pattern::from_chars(chars, shell.shopt.extglob);
+    if pattern.is_literal() {
+        Component::Literal(chars.iter().map(|&(c, _)| c).collect())
+    } else if shell.shopt.nocaseglob {
+        Component::Pattern(pattern.ignoring_case())
+    } else {
+        Component::Pattern(pattern)
+    }
+}
+
+/// Names in directory `dir`, skipping hidden ones unless `dotglob` is set or `dot_matches`
+fn entries(shell: &Shell, dir: &str, dot_matches: bool) -> Vec<String> {
+    let read_dir = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = fs::read_dir(read_dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.') || shell.shopt.dotglob || dot_matches)
+        .collect()
+}
+
+/// Paths of the entries in directory `dir` whose names match `pattern`
+fn matching_entries(
+    shell: &Shell,
+    dir: &str,
+    pattern: &Pattern,
+    directories_only: bool,
+) -> Vec<String> {
+    entries(shell, dir, pattern.starts_with_dot())
+        .into_iter()
+        .filter(|name| pattern.matches(name))
+        .map(|name| join(dir, &name))
+        .filter(|path| !directories_only || Path::new(path).is_dir())
+        .collect()
+}
+
+/// Collect everything below `dir`, or only directories, without following symbolic links
+fn descendants(shell: &Shell, dir: &str, directories_only: bool, found: &mut Vec<String>) {
+    for name in entries(shell, dir, false) {
+        let path = join(dir, &name);
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_dir());
+
+        if is_dir || !directories_only {
+            found.push(path.clone());
+        }
+        if is_dir {
+            descendants(shell, &path, directories_only, found);
+        }
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else if dir.ends_with('/') {
+        format!("{dir}{name}")
+    } else {
+        format!("{dir}/{name}")
+    }
+}
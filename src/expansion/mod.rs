@@ -0,0 +1,444 @@
+mod brace;
+mod glob;
+mod parameter;
+mod pattern;
+mod substitution;
+mod tilde;
+
+use thiserror::Error;
+
+use crate::arithmetic::{ArithmeticError, evaluate};
+use crate::parser::ParseError;
+use crate::parser::ast::Word;
+use crate::shell::Shell;
+use crate::variables::VariableError;
+
+use brace::expand_braces;
+use glob::expand_pathnames;
+use parameter::{Expansion, expand_dollar};
+use pattern::Pattern;
+use substitution::{substitute, take_backquoted};
+use tilde::expand_tilde;
+
+/// Errors that abort the expansion of a command's words
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ExpansionError {
+    #[error("{0}: bad substitution")]
+    BadSubstitution(String),
+    #[error("{0}: {1}")]
+    Unset(String, String),
+    #[error("${0}: cannot assign in this way")]
+    CannotAssign(String),
+    #[error("{0}: substring expression < 0")]
+    SubstringLength(i64),
+    #[error("no match: {0}")]
+    NoMatch(String),
+    #[error(transparent)]
+    Arithmetic(#[from] ArithmeticError),
+    #[error("command substitution: {0}")]
+    CommandSubstitution(String),
+    #[error(transparent)]
+    Syntax(#[from] ParseError),
+    #[error(transparent)]
+    Variable(#[from] VariableError),
+}
+
+impl ExpansionError {
+    /// Whether a shell running a script or `-c` command exits on the error, rather than
+    /// failing only the command it happened in
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::Unset(..) | Self::BadSubstitution(_) | Self::NoMatch(_)
+        )
+    }
+}
+
+/// Default field separators used when splitting unquoted expansions
+const DEFAULT_IFS: &str = " \t\n";
+
+/// Where a character of an expanded word came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    /// Unquoted text written directly in the word
+    Literal,
+    /// Text protected by quotes or a backslash
+    Quoted,
+    /// Unquoted result of an expansion, subject to field splitting
+    Expanded,
+}
+
+/// A field being assembled during expansion
+#[derive(Debug, Clone, Default)]
+struct Field {
+    chars: Vec<(char, Origin)>,
+    /// Whether the field contained quotes, which keeps it even when empty
+    quoted: bool,
+}
+
+impl Field {
+    fn push_str(&mut self, text: &str, origin: Origin) {
+        self.chars.extend(text.chars().map(|c| (c, origin)));
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().map(|&(c, _)| c).collect()
+    }
+}
+
+/// Character cursor over the source text of a word
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    /// Consume characters while the predicate holds
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.peek().filter(|&c| predicate(c)) {
+            self.pos += 1;
+            taken.push(c);
+        }
+        taken
+    }
+
+    /// Consume a bracketed span starting at the cursor, returning the text between the brackets
+    fn take_balanced(&mut self, open: char, close: char) -> String {
+        self.pos += 1;
+        let start = self.pos;
+        let mut depth = 1;
+        let mut quote = None;
+
+        while let Some(c) = self.bump() {
+            match (quote, c) {
+                (_, '\\') if quote != Some('\'') => {
+                    self.pos += 1;
+                }
+                (None, '\'' | '"' | '`') => quote = Some(c),
+                (Some(q), _) if q == c => quote = None,
+                (None, _) if c == open => depth += 1,
+                (None, _) if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.chars[start..self.pos - 1].iter().collect();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.chars[start.min(self.chars.len())..].iter().collect()
+    }
+}
+
+/// Expand a list of words into command arguments, expanding braces first and then
+/// splitting fields and matching pathnames
+pub fn expand_words(shell: &mut Shell, words: &[Word]) -> Result<Vec<String>, ExpansionError> {
+    let mut fields = Vec::new();
+    for word in words.iter().flat_map(|word| expand_braces(&word.0)) {
+        fields.extend(expand(shell, &word)?);
+    }
+    let ifs = shell.variables.get("IFS").unwrap_or(DEFAULT_IFS);
+
+    let mut words = Vec::new();
+    for field in fields.into_iter().flat_map(|field| split_field(field, ifs)) {
+        words.extend(expand_pathnames(shell, field)?);
+    }
+    Ok(words)
+}
+
+/// Expand a single word without field splitting, as for redirection targets
+pub fn expand_word(shell: &mut Shell, word: &Word) -> Result<String, ExpansionError> {
+    expand_text(shell, &word.0)
+}
+
+/// Expand an arithmetic expression, as in `$((...))` or `((...))`, and evaluate it
+pub fn expand_arithmetic(shell: &mut Shell, raw: &str) -> Result<i64, ExpansionError> {
+    let expression = expand_text(shell, raw)?;
+    Ok(evaluate(&mut shell.variables, &expression)?)
+}
+
+/// Expand source text into a single string without field splitting
+fn expand_text(shell: &mut Shell, raw: &str) -> Result<String, ExpansionError> {
+    Ok(expand(shell, raw)?
+        .iter()
+        .map(Field::text)
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Expand source text into a pattern, where quoted characters match literally
+fn expand_pattern(shell: &mut Shell, raw: &str) -> Result<Pattern, ExpansionError> {
+    let chars = expand(shell, raw)?
+        .into_iter()
+        .flat_map(|field| field.chars)
+        .collect::<Vec<_>>();
+    Ok(Pattern::from_chars(&chars, shell.shopt.extglob))
+}
+
+/// Whether `text` matches a pattern word after expansion, as `case` tests its patterns
+pub fn pattern_matches(
+    shell: &mut Shell,
+    pattern: &Word,
+    text: &str,
+) -> Result<bool, ExpansionError> {
+    Ok(expand_pattern(shell, &pattern.0)?.matches(text))
+}
+
+/// Expand the body of a here-document: parameters, arithmetic and command substitutions
+/// are expanded, and a backslash only escapes `$`, `` ` ``, `\\` and newlines
+pub fn expand_heredoc(shell: &mut Shell, body: &str) -> Result<String, ExpansionError> {
+    let mut cursor = Cursor::new(body);
+    let mut fields = vec![Field::default()];
+
+    while let Some(c) = cursor.bump() {
+        match c {
+            '\\' => match cursor.bump() {
+                Some('\n') => {}
+                Some(escaped @ ('$' | '`' | '\\')) => fields
+                    .last_mut()
+                    .unwrap()
+                    .chars
+                    .push((escaped, Origin::Quoted)),
+                Some(other) => fields
+                    .last_mut()
+                    .unwrap()
+                    .push_str(&format!("\\{other}"), Origin::Quoted),
+                None => fields
+                    .last_mut()
+                    .unwrap()
+                    .chars
+                    .push(('\\', Origin::Quoted)),
+            },
+            '`' => {
+                let source = take_backquoted(&mut cursor, false);
+                push_expansion(&mut fields, substitute(shell, &source)?, Origin::Quoted);
+            }
+            '$' => match expand_dollar(shell, &mut cursor)? {
+                Some(expansion) => push_expansion(&mut fields, expansion, Origin::Quoted),
+                None => fields.last_mut().unwrap().chars.push(('$', Origin::Quoted)),
+            },
+            _ => fields.last_mut().unwrap().chars.push((c, Origin::Quoted)),
+        }
+    }
+
+    Ok(fields.iter().map(Field::text).collect::<Vec<_>>().join(" "))
+}
+
+/// Expand the value of a `NAME=value` assignment, where a tilde may also follow any `:`
+pub fn expand_assignment(shell: &mut Shell, word: &Word) -> Result<String, ExpansionError> {
+    Ok(expand_source(shell, &word.0, true)?
+        .iter()
+        .map(Field::text)
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Perform tilde, parameter and arithmetic expansion, command substitution and quote removal on a word
+fn expand(shell: &mut Shell, raw: &str) -> Result<Vec<Field>, ExpansionError> {
+    expand_source(shell, raw, false)
+}
+
+fn expand_source(
+    shell: &mut Shell,
+    raw: &str,
+    assignment: bool,
+) -> Result<Vec<Field>, ExpansionError> {
+    let mut cursor = Cursor::new(raw);
+    let mut fields = vec![Field::default()];
+
+    while let Some(c) = cursor.bump() {
+        let field = fields.last().unwrap();
+        let tilde_allowed =
+            cursor.pos == 1 || (assignment && field.chars.last() == Some(&(':', Origin::Literal)));
+
+        match c {
+            '~' if tilde_allowed
+                && let Some(directory) = expand_tilde(shell, &mut cursor, assignment) =>
+            {
+                fields
+                    .last_mut()
+                    .unwrap()
+                    .push_str(&directory, Origin::Quoted);
+            }
+            '\\' => {
+                let field = fields.last_mut().unwrap();
+                if let Some(escaped) = cursor.bump() {
+                    field.chars.push((escaped, Origin::Quoted));
+                }
+                field.quoted = true;
+            }
+            '\'' => {
+                let text = cursor.take_while(|c| c != '\'');
+                cursor.bump();
+
+                let field = fields.last_mut().unwrap();
+                field.push_str(&text, Origin::Quoted);
+                field.quoted = true;
+            }
+            '"' => expand_double_quoted(shell, &mut cursor, &mut fields)?,
+            '`' => {
+                let source = take_backquoted(&mut cursor, false);
+                push_expansion(&mut fields, substitute(shell, &source)?, Origin::Expanded);
+            }
+            '$' => match expand_dollar(shell, &mut cursor)? {
+                Some(expansion) => push_expansion(&mut fields, expansion, Origin::Expanded),
+                None => fields
+                    .last_mut()
+                    .unwrap()
+                    .chars
+                    .push(('$', Origin::Literal)),
+            },
+            _ => fields.last_mut().unwrap().chars.push((c, Origin::Literal)),
+        }
+    }
+
+    Ok(fields)
+}
+
+/// Expand the inside of a double-quoted string, up to and including the closing quote
+fn expand_double_quoted(
+    shell: &mut Shell,
+    cursor: &mut Cursor,
+    fields: &mut Vec<Field>,
+) -> Result<(), ExpansionError> {
+    let mut vanished = false;
+    let start = fields.len();
+
+    while let Some(c) = cursor.bump() {
+        match c {
+            '"' => break,
+            '\\' => {
+                let field = fields.last_mut().unwrap();
+                match cursor.bump() {
+                    Some(escaped @ ('$' | '`' | '"' | '\\')) => {
+                        field.chars.push((escaped, Origin::Quoted))
+                    }
+                    Some(other) => field.push_str(&format!("\\{other}"), Origin::Quoted),
+                    None => field.chars.push(('\\', Origin::Quoted)),
+                }
+            }
+            '`' => {
+                let source = take_backquoted(cursor, true);
+                push_expansion(fields, substitute(shell, &source)?, Origin::Quoted);
+            }
+            '$' => match expand_dollar(shell, cursor)? {
+                Some(Expansion::Fields(values)) if values.is_empty() => vanished = true,
+                Some(expansion) => push_expansion(fields, expansion, Origin::Quoted),
+                None => fields.last_mut().unwrap().chars.push(('$', Origin::Quoted)),
+            },
+            _ => fields.last_mut().unwrap().chars.push((c, Origin::Quoted)),
+        }
+    }
+
+    // `"$@"` with no values produces no field at all rather than an empty one
+    let unchanged = fields.len() == start;
+    let field = fields.last_mut().unwrap();
+    if !(vanished && unchanged && field.chars.is_empty()) {
+        field.quoted = true;
+    }
+
+    Ok(())
+}
+
+/// Append an expansion result, starting new fields for multi-field expansions
+fn push_expansion(fields: &mut Vec<Field>, expansion: Expansion, origin: Origin) {
+    match expansion {
+        Expansion::Text(text) => fields.last_mut().unwrap().push_str(&text, origin),
+        Expansion::Fields(values) => {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    fields.push(Field {
+                        quoted: origin == Origin::Quoted,
+                        ..Field::default()
+                    });
+                }
+                fields.last_mut().unwrap().push_str(value, origin);
+            }
+        }
+        Expansion::Word(word) => {
+            for (i, field) in word.into_iter().enumerate() {
+                if i > 0 {
+                    fields.push(Field::default());
+                }
+
+                let current = fields.last_mut().unwrap();
+                current.quoted |= field.quoted;
+                current
+                    .chars
+                    .extend(field.chars.into_iter().map(|(c, inner)| {
+                        if origin == Origin::Quoted || inner == Origin::Quoted {
+                            (c, Origin::Quoted)
+                        } else {
+                            (c, Origin::Expanded)
+                        }
+                    }));
+            }
+        }
+    }
+}
+
+/// Split a field on unquoted IFS characters produced by expansions
+fn split_field(field: Field, ifs: &str) -> Vec<Field> {
+    let is_delimiter =
+        |&(c, origin): &(char, Origin)| origin == Origin::Expanded && ifs.contains(c);
+    let is_blank = |entry: &(char, Origin)| is_delimiter(entry) && entry.0.is_ascii_whitespace();
+
+    let mut fields = Vec::new();
+    let mut current = Field::default();
+    let mut started = false;
+    let mut i = 0;
+
+    while i < field.chars.len() {
+        if !is_delimiter(&field.chars[i]) {
+            current.chars.push(field.chars[i]);
+            started = true;
+            i += 1;
+            continue;
+        }
+
+        // A delimiter is a run of IFS whitespace around at most one other IFS character
+        while i < field.chars.len() && is_blank(&field.chars[i]) {
+            i += 1;
+        }
+        let hard = i < field.chars.len() && is_delimiter(&field.chars[i]);
+        if hard {
+            i += 1;
+            while i < field.chars.len() && is_blank(&field.chars[i]) {
+                i += 1;
+            }
+        }
+
+        if started || hard {
+            fields.push(std::mem::take(&mut current));
+        }
+        started = false;
+    }
+
+    if started {
+        fields.push(current);
+    }
+    if fields.is_empty() && field.quoted {
+        fields.push(field);
+    }
+
+    fields
+}
@@ -0,0 +1,473 @@
+use super::pattern::Pattern;
+use super::substitution::substitute;
+use super::{
+    Cursor, ExpansionError, Field, expand, expand_arithmetic, expand_pattern, expand_text,
+};
+use crate::shell::Shell;
+use crate::utils::is_valid_name;
+
+/// The result of expanding a parameter
+pub enum Expansion {
+    /// A single piece of text
+    Text(String),
+    /// Separate fields, as produced by `${name[@]}`
+    Fields(Vec<String>),
+    /// An already expanded word, as substituted by `${name:-word}`
+    Word(Vec<Field>),
+}
+
+/// The value of a parameter before any operator is applied
+enum Value {
+    Scalar(Option<String>),
+    /// Elements of `${name[@]}`, kept as separate fields, or `${name[*]}`, joined into one
+    List {
+        items: Vec<String>,
+        joined: bool,
+    },
+}
+
+impl Value {
+    /// Whether the parameter counts as set; with `null_is_unset`, an empty value does not
+    fn is_set(&self, null_is_unset: bool) -> bool {
+        match self {
+            Value::Scalar(value) => value
+                .as_ref()
+                .is_some_and(|value| !null_is_unset || !value.is_empty()),
+            Value::List { items, .. } => !items.is_empty(),
+        }
+    }
+
+    /// Apply a transformation to the value, or to each element of a list
+    fn map(self, f: impl Fn(&str) -> String) -> Value {
+        match self {
+            Value::Scalar(value) => Value::Scalar(Some(f(value.as_deref().unwrap_or_default()))),
+            Value::List { items, joined } => Value::List {
+                items: items.iter().map(|item| f(item)).collect(),
+                joined,
+            },
+        }
+    }
+
+    fn into_expansion(self, separator: &str) -> Expansion {
+        match self {
+            Value::Scalar(value) => Expansion::Text(value.unwrap_or_default()),
+            Value::List {
+                items,
+                joined: false,
+            } => Expansion::Fields(items),
+            Value::List {
+                items,
+                joined: true,
+            } => Expansion::Text(items.join(separator)),
+        }
+    }
+}
+
+/// Expand the parameter or command substitution following a `$`, returning `None`
+/// when the `$` is literal
+pub fn expand_dollar(
+    shell: &mut Shell,
+    cursor: &mut Cursor,
+) -> Result<Option<Expansion>, ExpansionError> {
+    let value = match cursor.peek() {
+        Some('{') => {
+            let inner = cursor.take_balanced('{', '}');
+            return braced(shell, &inner).map(Some);
+        }
+        Some('(') => {
+            let source = cursor.take_balanced('(', ')');
+            if let Some(expression) = arithmetic_body(&source) {
+                let value = expand_arithmetic(shell, expression)?;
+                return Ok(Some(Expansion::Text(value.to_string())));
+            }
+            return substitute(shell, &source).map(Some);
+        }
+        Some(c) if SPECIAL.contains(&c) => {
+            cursor.bump();
+            special(shell, c)
+        }
+        // Without braces, a positional parameter is a single digit: `$10` is `${1}0`
+        Some(c @ '0'..='9') => {
+            cursor.bump();
+            Value::Scalar(positional(shell, &c.to_string()))
+        }
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let name = cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            Value::Scalar(scalar(shell, &name))
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value.into_expansion(&separator(shell))))
+}
+
+/// Expand the contents of `${...}`
+fn braced(shell: &mut Shell, inner: &str) -> Result<Expansion, ExpansionError> {
+    let bad_substitution = || ExpansionError::BadSubstitution(format!("${{{inner}}}"));
+
+    if let Some(parameter) = inner.strip_prefix('#').filter(|rest| !rest.is_empty()) {
+        let mut cursor = Cursor::new(parameter);
+        let (_, value) = lookup(shell, &mut cursor).ok_or_else(bad_substitution)?;
+        if cursor.peek().is_some() {
+            return Err(bad_substitution());
+        }
+
+        let length = match value {
+            Value::Scalar(value) => value.unwrap_or_default().chars().count(),
+            Value::List { items, .. } => items.len(),
+        };
+        return Ok(Expansion::Text(length.to_string()));
+    }
+
+    let mut cursor = Cursor::new(inner);
+    let (name, value) = lookup(shell, &mut cursor).ok_or_else(bad_substitution)?;
+    let operation = cursor.chars[cursor.pos..].iter().collect::<String>();
+    let separator = separator(shell);
+
+    let (null_is_unset, operation) = match operation.strip_prefix(':') {
+        Some(rest) if rest.starts_with(['-', '=', '?', '+']) => (true, rest),
+        Some(rest) => return substring(shell, &name, value, rest, &separator),
+        None => (false, operation.as_str()),
+    };
+
+    let Some(op) = operation.chars().next() else {
+        return Ok(value.into_expansion(&separator));
+    };
+    let word = &operation[op.len_utf8()..];
+    let is_set = value.is_set(null_is_unset);
+
+    match op {
+        '-' if is_set => Ok(value.into_expansion(&separator)),
+        '-' => Ok(Expansion::Word(expand(shell, word)?)),
+        '+' if is_set => Ok(Expansion::Word(expand(shell, word)?)),
+        '+' => Ok(Expansion::Text(String::new())),
+        '=' if is_set => Ok(value.into_expansion(&separator)),
+        '=' => {
+            if !is_valid_name(&name) {
+                return Err(ExpansionError::CannotAssign(name));
+            }
+
+            let assigned = expand_text(shell, word)?;
+            shell.variables.set(&name, assigned.clone())?;
+            Ok(Expansion::Text(assigned))
+        }
+        '?' if is_set => Ok(value.into_expansion(&separator)),
+        '?' => {
+            let message = match expand_text(shell, word)? {
+                message if message.is_empty() => "parameter null or not set".to_string(),
+                message => message,
+            };
+            Err(ExpansionError::Unset(name, message))
+        }
+        '#' | '%' => {
+            let longest = word.starts_with(op);
+            let word = if longest { &word[1..] } else { word };
+            let pattern = expand_pattern(shell, word)?;
+
+            let value = value.map(|text| {
+                if op == '#' {
+                    let end = pattern.match_prefix(text, longest).unwrap_or(0);
+                    text[end..].to_string()
+                } else {
+                    let start = pattern.match_suffix(text, longest).unwrap_or(text.len());
+                    text[..start].to_string()
+                }
+            });
+            Ok(value.into_expansion(&separator))
+        }
+        '/' => {
+            let (mode, word) = match word.chars().next() {
+                Some(mode @ ('/' | '#' | '%')) => (Some(mode), &word[1..]),
+                _ => (None, word),
+            };
+            let (pattern, replacement) = split_unquoted(word, '/');
+            let pattern = expand_pattern(shell, pattern)?;
+            let replacement = match replacement {
+                Some(replacement) => expand_text(shell, replacement)?,
+                None => String::new(),
+            };
+
+            let value = value.map(|text| replace(text, &pattern, &replacement, mode));
+            Ok(value.into_expansion(&separator))
+        }
+        _ => Err(bad_substitution()),
+    }
+}
+
+/// Parse the parameter at the start of a `${...}` body
+fn lookup(shell: &Shell, cursor: &mut Cursor) -> Option<(String, Value)> {
+    match cursor.peek()? {
+        c if SPECIAL.contains(&c) => {
+            cursor.bump();
+            Some((c.to_string(), special(shell, c)))
+        }
+        c if c.is_ascii_digit() => {
+            let number = cursor.take_while(|c| c.is_ascii_digit());
+            let value = Value::Scalar(positional(shell, &number));
+            Some((number, value))
+        }
+        c if c.is_ascii_alphabetic() || c == '_' => {
+            let name = cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            if cursor.peek() != Some('[') {
+                let value = Value::Scalar(scalar(shell, &name));
+                return Some((name, value));
+            }
+
+            cursor.bump();
+            let subscript = cursor.take_while(|c| c != ']');
+            cursor.bump()?;
+
+            let items = array(shell, &name)
+                .or_else(|| {
+                    shell
+                        .variables
+                        .get(&name)
+                        .map(|value| vec![value.to_string()])
+                })
+                .unwrap_or_default();
+
+            let value = match subscript.as_str() {
+                "@" => Value::List {
+                    items,
+                    joined: false,
+                },
+                "*" => Value::List {
+                    items,
+                    joined: true,
+                },
+                index => Value::Scalar(
+                    index
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| items.get(i).cloned()),
+                ),
+            };
+            Some((name, value))
+        }
+        _ => None,
+    }
+}
+
+/// Apply `${name:offset}` or `${name:offset:length}`; slices of `$@` and `$*` count
+/// the positional parameters from 1, with `$0` at offset 0
+fn substring(
+    shell: &mut Shell,
+    name: &str,
+    value: Value,
+    operation: &str,
+    separator: &str,
+) -> Result<Expansion, ExpansionError> {
+    let (offset, length) = match split_unquoted(operation, ':') {
+        (offset, Some(length)) => (offset, Some(integer(shell, length)?)),
+        (offset, None) => (offset, None),
+    };
+    let offset = integer(shell, offset)?;
+
+    let slice = |len: usize| -> Result<(usize, usize), ExpansionError> {
+        let len = len as i64;
+        let start = if offset < 0 {
+            (len + offset).max(0)
+        } else {
+            offset.min(len)
+        };
+        let end = match length {
+            None => len,
+            Some(length) if length < 0 => len + length,
+            Some(length) => (start + length).min(len),
+        };
+        if end < start {
+            return Err(ExpansionError::SubstringLength(length.unwrap_or_default()));
+        }
+        Ok((start as usize, end as usize))
+    };
+
+    match value {
+        Value::Scalar(value) => {
+            let chars = value.unwrap_or_default().chars().collect::<Vec<_>>();
+            let (start, end) = slice(chars.len())?;
+            Ok(Expansion::Text(chars[start..end].iter().collect()))
+        }
+        Value::List { items, joined } if name == "@" || name == "*" => {
+            if let Some(length) = length.filter(|&length| length < 0) {
+                return Err(ExpansionError::SubstringLength(length));
+            }
+            let items = std::iter::once(shell.name.clone())
+                .chain(items)
+                .collect::<Vec<_>>();
+            // A negative offset reaching before `$0` selects nothing
+            if offset < 0 && offset.unsigned_abs() > items.len() as u64 {
+                let items = Vec::new();
+                return Ok(Value::List { items, joined }.into_expansion(separator));
+            }
+            let (start, end) = slice(items.len())?;
+            let items = items[start..end].to_vec();
+            Ok(Value::List { items, joined }.into_expansion(separator))
+        }
+        Value::List { items, joined } => {
+            let (start, end) = slice(items.len())?;
+            let items = items[start..end].to_vec();
+            Ok(Value::List { items, joined }.into_expansion(separator))
+        }
+    }
+}
+
+/// Evaluate an offset or length of a substring expansion
+fn integer(shell: &mut Shell, text: &str) -> Result<i64, ExpansionError> {
+    expand_arithmetic(shell, text)
+}
+
+/// The expression of `$((expression))`, given the text between the outer parentheses,
+/// or `None` for a command substitution such as `$( (cd dir) )`
+fn arithmetic_body(source: &str) -> Option<&str> {
+    let inner = source.strip_prefix('(')?.strip_suffix(')')?;
+    let mut cursor = Cursor::new(source);
+    cursor.take_balanced('(', ')');
+    (cursor.pos == source.chars().count()).then_some(inner)
+}
+
+/// Replace matches of `pattern`: the first one, every one (`/`), or only at the start (`#`) or end (`%`)
+fn replace(text: &str, pattern: &Pattern, replacement: &str, mode: Option<char>) -> String {
+    match mode {
+        Some('#') => match pattern.match_prefix(text, true) {
+            Some(end) => format!("{replacement}{}", &text[end..]),
+            None => text.to_string(),
+        },
+        Some('%') => match pattern.match_suffix(text, true) {
+            Some(start) => format!("{}{replacement}", &text[..start]),
+            None => text.to_string(),
+        },
+        _ => {
+            let mut result = String::new();
+            let mut pos = 0;
+
+            let limit = if mode == Some('/') { usize::MAX } else { 1 };
+            for (start, end) in pattern.find_all(text).take(limit) {
+                result.push_str(&text[pos..start]);
+                result.push_str(replacement);
+                pos = end;
+            }
+
+            result.push_str(&text[pos..]);
+            result
+        }
+    }
+}
+
+/// Split a word at the first occurrence of `delimiter` outside quotes and nested braces
+fn split_unquoted(word: &str, delimiter: char) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut depth = 0;
+    let mut escaped = false;
+
+    for (i, c) in word.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some('"'), '"') => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => depth -= 1,
+            (None, _) if c == delimiter && depth == 0 => {
+                return (&word[..i], Some(&word[i + c.len_utf8()..]));
+            }
+            _ => {}
+        }
+    }
+
+    (word, None)
+}
+
+/// Separator for `${name[*]}`: the first character of IFS, or a space when IFS is unset
+fn separator(shell: &Shell) -> String {
+    match shell.variables.get("IFS") {
+        Some(ifs) => ifs.chars().take(1).collect(),
+        None => " ".to_string(),
+    }
+}
+
+/// Special parameters named by a single punctuation character
+const SPECIAL: &[char] = &['?', '!', '#', '@', '*'];
+
+/// Value of a special parameter: `$?` is the last status, `$!` the process ID of the
+/// most recent background job, `$#` the number of positional parameters, and `$@` and
+/// `$*` the positional parameters themselves
+fn special(shell: &Shell, c: char) -> Value {
+    match c {
+        '?' => Value::Scalar(Some(shell.last_status.to_string())),
+        '!' => Value::Scalar(shell.last_background.map(|pid| pid.to_string())),
+        '#' => Value::Scalar(Some(shell.positional.len().to_string())),
+        '@' | '*' => Value::List {
+            items: shell.positional.clone(),
+            joined: c == '*',
+        },
+        _ => unreachable!("not a special parameter: {c}"),
+    }
+}
+
+/// Value of the positional parameter with the given number, or `None` if it is unset;
+/// `$0` is the name of the shell or of the script it runs
+fn positional(shell: &Shell, number: &str) -> Option<String> {
+    match number.parse::<usize>().ok()? {
+        0 => Some(shell.name.clone()),
+        number => shell.positional.get(number - 1).cloned(),
+    }
+}
+
+/// Value of a named parameter, or `None` if it is unset
+fn scalar(shell: &Shell, name: &str) -> Option<String> {
+    match array(shell, name) {
+        Some(values) => values.into_iter().next(),
+        None => shell.variables.get(name).map(str::to_string),
+    }
+}
+
+/// Look up the elements of an array parameter
+fn array(shell: &Shell, name: &str) -> Option<Vec<String>> {
+    match name {
+        "PIPESTATUS" => Some(shell.pipestatus.iter().map(i32::to_string).collect()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::expand_words;
+    use crate::parser::ast::Word;
+
+    /// Expand `word` into fields with `a b c` as the positional parameters
+    fn expand_positional(word: &str) -> Result<Vec<String>, ExpansionError> {
+        let mut shell = Shell {
+            name: "rsh".to_string(),
+            positional: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            ..Shell::default()
+        };
+        expand_words(&mut shell, &[Word(word.to_string())])
+    }
+
+    #[test]
+    fn positional_slices_count_from_one() {
+        assert_eq!(expand_positional("\"${@:2}\"").unwrap(), ["b", "c"]);
+        assert_eq!(expand_positional("\"${*:1:2}\"").unwrap(), ["a b"]);
+        assert_eq!(expand_positional("${@:0}").unwrap(), ["rsh", "a", "b", "c"]);
+        assert_eq!(expand_positional("${@: -1}").unwrap(), ["c"]);
+        assert_eq!(expand_positional("${@: -4:2}").unwrap(), ["rsh", "a"]);
+        assert!(expand_positional("${@: -5}").unwrap().is_empty());
+        assert!(expand_positional("${@:1:-1}").is_err());
+    }
+
+    #[test]
+    fn special_slices_index_like_positional_parameters() {
+        for number in 0..=3 {
+            assert_eq!(
+                expand_positional(&format!("${{@:{number}:1}}")).unwrap(),
+                expand_positional(&format!("${number}")).unwrap(),
+            );
+        }
+        assert_eq!(expand_positional("${#}").unwrap(), ["3"]);
+        assert_eq!(expand_positional("${*:3:1}").unwrap(), ["c"]);
+    }
+}
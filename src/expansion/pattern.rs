@@ -0,0 +1,470 @@
+use super::Origin;
+
+/// One element of a compiled pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[...]` bracket expression
+    Class {
+        negated: bool,
+        items: Vec<ClassItem>,
+    },
+    /// An extglob group such as `@(a|b)`, with the operator character that introduced it
+    Group {
+        operator: char,
+        alternatives: Vec<Vec<Token>>,
+    },
+}
+
+/// Member of a bracket expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    /// A character class such as `[:alpha:]`
+    Named(String),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ClassItem::Char(item) => *item == c,
+            ClassItem::Range(start, end) => (*start..=*end).contains(&c),
+            ClassItem::Named(name) => match name.as_str() {
+                "alpha" => c.is_alphabetic(),
+                "digit" => c.is_ascii_digit(),
+                "alnum" => c.is_alphanumeric(),
+                "upper" => c.is_uppercase(),
+                "lower" => c.is_lowercase(),
+                "space" => c.is_whitespace(),
+                "blank" => c == ' ' || c == '\t',
+                "punct" => c.is_ascii_punctuation(),
+                "print" => !c.is_control(),
+                "graph" => !c.is_control() && !c.is_whitespace(),
+                "cntrl" => c.is_control(),
+                "xdigit" => c.is_ascii_hexdigit(),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Token {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        if !ignore_case {
+            return self.matches_exactly(c);
+        }
+
+        self.matches_exactly(c)
+            || c.to_lowercase().any(|lower| self.matches_exactly(lower))
+            || c.to_uppercase().any(|upper| self.matches_exactly(upper))
+    }
+
+    fn matches_exactly(&self, c: char) -> bool {
+        match self {
+            Token::Literal(literal) => *literal == c,
+            Token::AnyChar => true,
+            Token::Class { negated, items } => items.iter().any(|item| item.matches(c)) != *negated,
+            Token::AnyString | Token::Group { .. } => {
+                unreachable!("`*` and groups match strings, not characters")
+            }
+        }
+    }
+}
+
+/// A shell pattern, as used by `${var#pattern}` and friends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+    ignore_case: bool,
+}
+
+impl Pattern {
+    /// Compile a pattern from expanded characters, treating quoted ones literally;
+    /// with `extglob`, `?(...)`, `*(...)`, `+(...)`, `@(...)` and `!(...)` form groups
+    pub(super) fn from_chars(chars: &[(char, Origin)], extglob: bool) -> Self {
+        Self {
+            tokens: compile(chars, extglob),
+            ignore_case: false,
+        }
+    }
+
+    /// Make the pattern match letters regardless of case
+    pub fn ignoring_case(mut self) -> Self {
+        self.ignore_case = true;
+        self
+    }
+
+    /// Whether the pattern contains no wildcards, so it can only match its own text
+    pub fn is_literal(&self) -> bool {
+        self.tokens
+            .iter()
+            .all(|token| matches!(token, Token::Literal(_)))
+    }
+
+    /// Whether the pattern can only match names starting with `.`
+    pub fn starts_with_dot(&self) -> bool {
+        self.tokens.first() == Some(&Token::Literal('.'))
+    }
+
+    /// Whether the pattern matches the whole of `text`
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.chars().collect::<Vec<_>>();
+        self.ends(&text, 0).last() == Some(&text.len())
+    }
+
+    /// Byte length of the shortest or longest prefix of `text` the pattern matches
+    pub fn match_prefix(&self, text: &str, longest: bool) -> Option<usize> {
+        let chars = text.chars().collect::<Vec<_>>();
+        let ends = self.ends(&chars, 0);
+        let end = if longest { ends.last() } else { ends.first() };
+        end.map(|&end| char_boundaries(text)[end])
+    }
+
+    /// Byte offset of the shortest or longest suffix of `text` the pattern matches
+    pub fn match_suffix(&self, text: &str, longest: bool) -> Option<usize> {
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut starts = (0..=chars.len()).collect::<Vec<_>>();
+        if !longest {
+            starts.reverse();
+        }
+        let start = starts
+            .into_iter()
+            .find(|&start| self.ends(&chars, start).last() == Some(&chars.len()))?;
+        Some(char_boundaries(text)[start])
+    }
+
+    /// Byte ranges of the successive non-empty matches in `text`, each the longest one
+    /// starting at the first position that has one, searching on from where it ended
+    pub fn find_all<'a>(&'a self, text: &str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let chars = text.chars().collect::<Vec<_>>();
+        let boundaries = char_boundaries(text);
+        let mut start = 0;
+
+        std::iter::from_fn(move || {
+            while start < chars.len() {
+                match self.ends(&chars, start).last() {
+                    Some(&end) if end > start => {
+                        let range = (boundaries[start], boundaries[end]);
+                        start = end;
+                        return Some(range);
+                    }
+                    _ => start += 1,
+                }
+            }
+            None
+        })
+    }
+
+    /// Character positions at which a match of the pattern starting at `start` can end
+    fn ends(&self, text: &[char], start: usize) -> Vec<usize> {
+        match_ends(&self.tokens, text, vec![start], self.ignore_case)
+    }
+}
+
+/// Every byte offset of `text` that lies on a character boundary, including its end
+fn char_boundaries(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect()
+}
+
+/// Compile pattern characters into tokens
+fn compile(chars: &[(char, Origin)], extglob: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (c, origin) = chars[i];
+        i += 1;
+
+        if origin == Origin::Quoted {
+            tokens.push(Token::Literal(c));
+            continue;
+        }
+
+        if extglob
+            && matches!(c, '?' | '*' | '+' | '@' | '!')
+            && chars.get(i) == Some(&('(', origin))
+            && let Some((alternatives, consumed)) = parse_group(&chars[i + 1..], extglob)
+        {
+            tokens.push(Token::Group {
+                operator: c,
+                alternatives,
+            });
+            i += 1 + consumed;
+            continue;
+        }
+
+        match c {
+            '\\' if i < chars.len() => {
+                tokens.push(Token::Literal(chars[i].0));
+                i += 1;
+            }
+            '?' => tokens.push(Token::AnyChar),
+            '*' => {
+                if tokens.last() != Some(&Token::AnyString) {
+                    tokens.push(Token::AnyString);
+                }
+            }
+            '[' => match parse_class(&chars[i..]) {
+                Some((class, consumed)) => {
+                    tokens.push(class);
+                    i += consumed;
+                }
+                None => tokens.push(Token::Literal('[')),
+            },
+            _ => tokens.push(Token::Literal(c)),
+        }
+    }
+
+    tokens
+}
+
+/// Parse the `|`-separated alternatives of an extglob group following its `(`,
+/// returning them with the number of characters used, including the closing `)`
+fn parse_group(chars: &[(char, Origin)], extglob: bool) -> Option<(Vec<Vec<Token>>, usize)> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, &(c, origin)) in chars.iter().enumerate() {
+        if origin == Origin::Quoted {
+            continue;
+        }
+
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            '|' | ')' if depth == 0 => {
+                alternatives.push(compile(&chars[start..i], extglob));
+                start = i + 1;
+                if c == ')' {
+                    return Some((alternatives, i + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Every position at which `tokens` can finish matching `text`, having started at any
+/// of the positions in `starts`. The text is scanned forward once per token, keeping
+/// only the positions still reachable, so matching never backtracks; positions are
+/// sorted and distinct
+fn match_ends(
+    tokens: &[Token],
+    text: &[char],
+    mut positions: Vec<usize>,
+    ignore_case: bool,
+) -> Vec<usize> {
+    for token in tokens {
+        if positions.is_empty() {
+            break;
+        }
+
+        positions = match token {
+            Token::AnyString => (positions[0]..=text.len()).collect(),
+            Token::Group {
+                operator,
+                alternatives,
+            } => {
+                let mut ends = Vec::new();
+                for &start in &positions {
+                    ends.extend(match_group(
+                        *operator,
+                        alternatives,
+                        text,
+                        start,
+                        ignore_case,
+                    ));
+                }
+                sorted(ends)
+            }
+            token => positions
+                .into_iter()
+                .filter(|&i| text.get(i).is_some_and(|&c| token.matches(c, ignore_case)))
+                .map(|i| i + 1)
+                .collect(),
+        };
+    }
+
+    positions
+}
+
+/// Positions at which an extglob group starting at `start` can end
+fn match_group(
+    operator: char,
+    alternatives: &[Vec<Token>],
+    text: &[char],
+    start: usize,
+    ignore_case: bool,
+) -> Vec<usize> {
+    let any = |starts: Vec<usize>| {
+        let ends = alternatives
+            .iter()
+            .flat_map(|alternative| match_ends(alternative, text, starts.clone(), ignore_case));
+        sorted(ends.collect())
+    };
+
+    match operator {
+        '@' => any(vec![start]),
+        '?' => sorted([start].into_iter().chain(any(vec![start])).collect()),
+        '*' | '+' => {
+            // Repeat the alternatives from the newly reached positions until none are new
+            let mut seen = vec![false; text.len() + 1];
+            let mut reached = Vec::new();
+            if operator == '*' {
+                seen[start] = true;
+                reached.push(start);
+            }
+            let mut frontier = vec![start];
+            loop {
+                frontier = any(frontier);
+                frontier.retain(|&end| !std::mem::replace(&mut seen[end], true));
+                if frontier.is_empty() {
+                    break sorted(reached);
+                }
+                reached.extend(&frontier);
+            }
+        }
+        _ => {
+            let excluded = any(vec![start]);
+            (start..=text.len())
+                .filter(|end| excluded.binary_search(end).is_err())
+                .collect()
+        }
+    }
+}
+
+/// Positions sorted with duplicates removed
+fn sorted(mut positions: Vec<usize>) -> Vec<usize> {
+    positions.sort_unstable();
+    positions.dedup();
+    positions
+}
+
+/// Parse a bracket expression following `[`, returning it with the number of characters used
+fn parse_class(chars: &[(char, Origin)]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(
+        chars.first(),
+        Some(('!' | '^', Origin::Literal | Origin::Expanded))
+    );
+    if negated {
+        i += 1;
+    }
+
+    let mut items = Vec::new();
+    let start = i;
+
+    loop {
+        let &(c, origin) = chars.get(i)?;
+        let quoted = origin == Origin::Quoted;
+
+        if c == ']' && !quoted && i > start {
+            return Some((Token::Class { negated, items }, i + 1));
+        }
+
+        if c == '[' && !quoted && chars.get(i + 1).map(|&(c, _)| c) == Some(':') {
+            let rest = chars[i + 2..].iter().map(|&(c, _)| c).collect::<String>();
+            if let Some(end) = rest.find(":]") {
+                items.push(ClassItem::Named(rest[..end].to_string()));
+                i += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+        }
+
+        let c = if c == '\\' && !quoted {
+            i += 1;
+            chars.get(i)?.0
+        } else {
+            c
+        };
+
+        match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some(('-', _)), Some(&(end, _))) if end != ']' => {
+                items.push(ClassItem::Range(c, end));
+                i += 3;
+            }
+            _ => {
+                items.push(ClassItem::Char(c));
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compile an unquoted pattern, where a backslash makes the next character literal
+    fn pattern(text: &str) -> Pattern {
+        let chars = text
+            .chars()
+            .map(|c| (c, Origin::Literal))
+            .collect::<Vec<_>>();
+        Pattern::from_chars(&chars, true)
+    }
+
+    #[test]
+    fn wildcards_and_classes() {
+        assert!(pattern("*.rs").matches("main.rs"));
+        assert!(!pattern("*.rs").matches("main.rc"));
+        assert!(pattern("file?.[ch]").matches("file1.h"));
+        assert!(pattern("[!a-c]*").matches("dog"));
+        assert!(!pattern("[!a-c]*").matches("cat"));
+        assert!(pattern("[[:digit:]]x").matches("7x"));
+        assert!(pattern(r"\*").matches("*"));
+        assert!(!pattern(r"\*").matches("a"));
+    }
+
+    #[test]
+    fn extended_groups() {
+        assert!(pattern("@(foo|bar).rs").matches("bar.rs"));
+        assert!(!pattern("@(foo|bar).rs").matches("baz.rs"));
+        assert!(pattern("!(*.rs)").matches("notes.txt"));
+        assert!(!pattern("!(*.rs)").matches("main.rs"));
+        assert!(pattern("+(ab)c").matches("ababc"));
+        assert!(!pattern("+(ab)c").matches("c"));
+        assert!(pattern("*(ab)c").matches("c"));
+        assert!(pattern("x?(y)z").matches("xz"));
+        assert!(pattern("*.RS").ignoring_case().matches("main.rs"));
+    }
+
+    #[test]
+    fn prefixes_and_suffixes() {
+        let prefix = pattern("*/");
+        assert_eq!(prefix.match_prefix("a/b/c", false), Some(2));
+        assert_eq!(prefix.match_prefix("a/b/c", true), Some(4));
+
+        let suffix = pattern(".*");
+        assert_eq!(suffix.match_suffix("x.tar.gz", false), Some(5));
+        assert_eq!(suffix.match_suffix("x.tar.gz", true), Some(1));
+    }
+
+    #[test]
+    fn large_inputs_match_quickly() {
+        let text = "ab".repeat(10_000);
+        let started = std::time::Instant::now();
+
+        assert_eq!(pattern("a").find_all(&text).count(), 10_000);
+        assert_eq!(pattern("*b").find_all(&text).next(), Some((0, text.len())));
+        assert_eq!(
+            pattern("a*").match_suffix(&text, false),
+            Some(text.len() - 2)
+        );
+        assert!(pattern("*(ab)").matches(&text));
+        assert!(!pattern("*a*a*c").matches(&text));
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+}
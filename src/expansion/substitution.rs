@@ -0,0 +1,42 @@
+use super::parameter::Expansion;
+use super::{Cursor, ExpansionError};
+use crate::executor::capture_output;
+use crate::parser::parse;
+use crate::shell::Shell;
+
+/// Run the command inside `$(...)` or backquotes and substitute its output
+pub fn substitute(shell: &mut Shell, source: &str) -> Result<Expansion, ExpansionError> {
+    let list = parse(source)?;
+    let (output, status) = capture_output(&list, shell)
+        .map_err(|err| ExpansionError::CommandSubstitution(err.to_string()))?;
+    shell.substitution_status = Some(status);
+
+    let output = String::from_utf8_lossy(&output);
+    Ok(Expansion::Text(output.trim_end_matches('\n').to_string()))
+}
+
+/// Consume the body of a backquoted command up to the closing backquote, removing
+/// the backslashes that protect `$`, `` ` `` and `\` (and `"` inside double quotes)
+pub fn take_backquoted(cursor: &mut Cursor, in_double_quotes: bool) -> String {
+    let mut source = String::new();
+
+    while let Some(c) = cursor.bump() {
+        match c {
+            '`' => break,
+            '\\' => match cursor.peek() {
+                Some(escaped @ ('$' | '`' | '\\')) => {
+                    cursor.bump();
+                    source.push(escaped);
+                }
+                Some('"') if in_double_quotes => {
+                    cursor.bump();
+                    source.push('"');
+                }
+                _ => source.push('\\'),
+            },
+            _ => source.push(c),
+        }
+    }
+
+    source
+}
@@ -0,0 +1,49 @@
+use std::ffi::{CStr, CString};
+
+use super::Cursor;
+use crate::shell::Shell;
+
+/// Expand the tilde prefix following a `~` at the cursor, consuming it if it could be
+/// resolved; the prefix runs up to the first `/`, or `:` as well within assignments
+pub fn expand_tilde(shell: &Shell, cursor: &mut Cursor, assignment: bool) -> Option<String> {
+    let end = cursor.chars[cursor.pos..]
+        .iter()
+        .position(|&c| c == '/' || (assignment && c == ':'))
+        .map_or(cursor.chars.len(), |len| cursor.pos + len);
+    let prefix = cursor.chars[cursor.pos..end].iter().collect::<String>();
+
+    // Any quoting or expansion in the prefix keeps the tilde literal
+    if prefix.contains(['\'', '"', '\\', '$', '`']) {
+        return None;
+    }
+
+    let directory = match prefix.as_str() {
+        "" => match shell.variables.get("HOME") {
+            Some(home) => home.to_string(),
+            None => home_directory(None)?,
+        },
+        "+" => shell.variables.get("PWD")?.to_string(),
+        "-" => shell.variables.get("OLDPWD")?.to_string(),
+        user => home_directory(Some(user))?,
+    };
+
+    cursor.pos = end;
+    Some(directory)
+}
+
+/// Home directory from the passwd database, of the named user or else the current one
+fn home_directory(user: Option<&str>) -> Option<String> {
+    let entry = match user {
+        Some(user) => {
+            let name = CString::new(user).ok()?;
+            unsafe { libc::getpwnam(name.as_ptr()) }
+        }
+        None => unsafe { libc::getpwuid(libc::getuid()) },
+    };
+    if entry.is_null() {
+        return None;
+    }
+
+    let directory = unsafe { CStr::from_ptr((*entry).pw_dir) };
+    directory.to_str().ok().map(str::to_string)
+}
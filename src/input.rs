@@ -1,16 +1,16 @@
 use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers, read};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
-use std::io::{stdout, Write};
+use std::io::{Write, stdout};
 
-use crate::executor::run_sh;
+use crate::executor::{run_sh, run_trap};
+use crate::parser::is_incomplete;
+use crate::shell::Shell;
+use crate::traps::Trap;
 use crate::utils::lcp;
 
 /// Handle keyboard input loop for the shell
 #[allow(clippy::never_loop)]
-pub fn input_loop(
-    cmds: &[String],
-    local_history: &mut Vec<String>,
-) -> std::io::Result<()> {
+pub fn input_loop(cmds: &[String], shell: &mut Shell) -> std::io::Result<()> {
     let mut expect_completions = false;
 
     loop {
@@ -29,23 +29,35 @@ pub fn input_loop(
                     continue;
                 }
 
+                // Raw mode turns Ctrl-C into a key rather than SIGINT: it discards the
+                // line being typed, runs any INT trap and starts a new line
                 if k.modifiers.contains(KeyModifiers::CONTROL) && k.code.is_char('c') {
-                    return Ok(());
+                    command.clear();
+                    idx = 0;
+                    shell.last_status = 130;
+                    print!("^C\r\n");
+                    disable_raw_mode()?;
+                    run_trap(shell, Trap::Signal(libc::SIGINT))?;
+                    print!("$ ");
+                    stdout().flush()?;
+                    continue;
                 }
 
                 match k.code {
                     KeyCode::Tab => {
-                        handle_tab_completion(
-                            &mut command,
-                            cmds,
-                            &mut expect_completions,
-                        )?;
+                        handle_tab_completion(&mut command, cmds, &mut expect_completions)?;
                     }
                     KeyCode::Char('j') if k.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if is_incomplete(&command) {
+                            command.push('\n');
+                            print!("\r\n> ");
+                            stdout().flush()?;
+                            continue;
+                        }
                         disable_raw_mode()?;
-                        local_history.push(command.clone());
+                        shell.history.push(command.clone());
                         idx = 0;
-                        run_sh(&mut command, local_history)?;
+                        run_sh(&mut command, shell)?;
                         print!("\r$ ");
                         stdout().flush()?;
                     }
@@ -55,35 +67,35 @@ pub fn input_loop(
                         stdout().flush()?;
                     }
                     KeyCode::Enter => {
+                        if is_incomplete(&command) {
+                            command.push('\n');
+                            print!("\r\n> ");
+                            stdout().flush()?;
+                            continue;
+                        }
                         disable_raw_mode()?;
-                        local_history.push(command.clone());
+                        shell.history.push(command.clone());
                         idx = 0;
-                        run_sh(&mut command, local_history)?;
+                        run_sh(&mut command, shell)?;
                         print!("\r$ ");
                         stdout().flush()?;
                     }
-                    KeyCode::Up => {
-                        if !local_history.is_empty() && idx < local_history.len() {
-                            idx += 1;
-                            command = local_history[local_history.len() - idx].clone();
-                            print!("\r\x1b[2K$ {}", command);
-                            stdout().flush()?;
-                        }
+                    KeyCode::Up if !shell.history.is_empty() && idx < shell.history.len() => {
+                        idx += 1;
+                        command = shell.history[shell.history.len() - idx].clone();
+                        print!("\r\x1b[2K$ {}", command);
+                        stdout().flush()?;
                     }
-                    KeyCode::Down => {
-                        if !local_history.is_empty() && idx > 1 {
-                            idx -= 1;
-                            command = local_history[local_history.len() - idx].clone();
-                            print!("\r\x1b[2K$ {}", command);
-                            stdout().flush()?;
-                        }
+                    KeyCode::Down if !shell.history.is_empty() && idx > 1 => {
+                        idx -= 1;
+                        command = shell.history[shell.history.len() - idx].clone();
+                        print!("\r\x1b[2K$ {}", command);
+                        stdout().flush()?;
                     }
-                    KeyCode::Backspace => {
-                        if !command.is_empty() {
-                            command.pop();
-                            print!("\x08 \x08");
-                            stdout().flush()?;
-                        }
+                    KeyCode::Backspace if !command.is_empty() => {
+                        command.pop();
+                        print!("\x08 \x08");
+                        stdout().flush()?;
                     }
                     _ => {}
                 }
@@ -131,7 +143,7 @@ fn handle_tab_completion(
         if *expect_completions && possible_cmd.len() > 1 {
             disable_raw_mode()?;
             print!("\r\n");
-            print!("{}\n", possible_cmd.join("  "));
+            println!("{}", possible_cmd.join("  "));
             print!("$ {}", command);
             stdout().flush()?;
         } else {
@@ -0,0 +1,480 @@
+use std::ffi::CStr;
+use std::io;
+use std::os::fd::RawFd;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::shell::{Flow, Shell};
+use crate::signals;
+
+/// What became of one process of a job, as last reported by `waitpid`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    Running,
+    Stopped,
+    Exited(i32),
+    /// Killed by the given signal
+    Signaled(i32),
+}
+
+impl ProcessState {
+    /// Decode a status filled in by `waitpid`
+    fn from_wait_status(status: i32) -> Self {
+        if libc::WIFEXITED(status) {
+            ProcessState::Exited(libc::WEXITSTATUS(status))
+        } else if libc::WIFSIGNALED(status) {
+            ProcessState::Signaled(libc::WTERMSIG(status))
+        } else if libc::WIFSTOPPED(status) {
+            ProcessState::Stopped
+        } else {
+            ProcessState::Running
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(self, ProcessState::Exited(_) | ProcessState::Signaled(_))
+    }
+
+    /// Exit status as the shell reports it, with 128 added to the number of the signal
+    /// that killed or stopped the process
+    pub fn status(self) -> i32 {
+        match self {
+            ProcessState::Exited(status) => status,
+            ProcessState::Signaled(signal) => 128 + signal,
+            ProcessState::Stopped => 128 + libc::SIGTSTP,
+            ProcessState::Running => 0,
+        }
+    }
+}
+
+/// How long [`Job::update`] waits for the job's processes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wait {
+    /// Only collect changes that have already happened
+    NoHang,
+    /// Until every process has finished or stopped, as for a job in the foreground
+    UntilStopped,
+    /// Until every process has finished, as without job control
+    UntilFinished,
+}
+
+/// A process belonging to a job
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub pid: i32,
+    pub state: ProcessState,
+}
+
+/// A background or stopped pipeline tracked by the shell
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// Number used in `%n` job specs
+    pub id: usize,
+    pub processes: Vec<Process>,
+    /// Process group the job runs in, when the shell has job control
+    pub pgid: Option<i32>,
+    /// The command as it is shown by `jobs`, without a trailing `&`
+    pub command: String,
+}
+
+impl Job {
+    /// Process ID reported by `jobs -p` and `jobs -l`
+    pub fn pid(&self) -> i32 {
+        self.processes[0].pid
+    }
+
+    /// State of the job as a whole: running while any process runs, then stopped while
+    /// any is stopped, and otherwise finished like its last process
+    pub fn state(&self) -> ProcessState {
+        let states = self.processes.iter().map(|process| process.state);
+        if let Some(state) = states.clone().find(|state| !state.is_finished()) {
+            return match states.clone().any(|state| state == ProcessState::Running) {
+                true => ProcessState::Running,
+                false => state,
+            };
+        }
+        self.processes.last().unwrap().state
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state().is_finished()
+    }
+
+    /// Exit status of a finished job
+    pub fn status(&self) -> i32 {
+        self.state().status()
+    }
+
+    /// Collect status changes of the job's processes, blocking as `wait` says
+    pub fn update(&mut self, wait: Wait) -> io::Result<()> {
+        let flags = libc::WUNTRACED | libc::WCONTINUED;
+
+        for process in &mut self.processes {
+            while !process.state.is_finished() {
+                let blocking = match wait {
+                    Wait::NoHang => false,
+                    Wait::UntilStopped => process.state == ProcessState::Running,
+                    Wait::UntilFinished => true,
+                };
+                let flags = if blocking {
+                    flags
+                } else {
+                    flags | libc::WNOHANG
+                };
+
+                let mut status = 0;
+                match unsafe { libc::waitpid(process.pid, &mut status, flags) } {
+                    -1 => {
+                        let err = io::Error::last_os_error();
+                        match err.raw_os_error() {
+                            Some(libc::EINTR) => continue,
+                            // Reaped elsewhere, so there is no status left to collect
+                            Some(libc::ECHILD) => process.state = ProcessState::Exited(0),
+                            _ => return Err(err),
+                        }
+                    }
+                    0 => break,
+                    _ => process.state = ProcessState::from_wait_status(status),
+                }
+
+                if !blocking {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Let the job's stopped processes continue; with a process group of its own, the
+    /// whole group is signalled so processes the shell does not track continue too
+    pub fn resume(&mut self) -> io::Result<()> {
+        for process in &mut self.processes {
+            if process.state == ProcessState::Stopped {
+                process.state = ProcessState::Running;
+            }
+        }
+        let result = match self.pgid {
+            Some(pgid) => unsafe { libc::kill(-pgid, libc::SIGCONT) },
+            None => self
+                .processes
+                .iter()
+                .filter(|process| !process.state.is_finished())
+                .map(|process| unsafe { libc::kill(process.pid, libc::SIGCONT) })
+                .min()
+                .unwrap_or(0),
+        };
+
+        match result {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The shell's jobs, with the order in which they were last started or stopped so the
+/// current (`%+`) and previous (`%-`) jobs can be told apart
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    recent: Vec<usize>,
+}
+
+impl Jobs {
+    /// Add a job and make it the current one, returning its number
+    pub fn add(&mut self, processes: Vec<Process>, pgid: Option<i32>, command: String) -> usize {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            processes,
+            pgid,
+            command,
+        });
+        self.touch(id);
+        id
+    }
+
+    /// Make a job the current one
+    pub fn touch(&mut self, id: usize) {
+        self.recent.retain(|&recent| recent != id);
+        self.recent.push(id);
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        self.recent.retain(|&recent| recent != id);
+        Some(self.jobs.remove(index))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    /// Numbers of all jobs, in ascending order
+    pub fn ids(&self) -> Vec<usize> {
+        self.jobs.iter().map(|job| job.id).collect()
+    }
+
+    /// The job containing a process
+    pub fn find_pid(&self, pid: i32) -> Option<usize> {
+        self.jobs
+            .iter()
+            .find(|job| job.processes.iter().any(|process| process.pid == pid))
+            .map(|job| job.id)
+    }
+
+    /// `+` for the current job, `-` for the previous one and a space otherwise
+    pub fn marker(&self, id: usize) -> char {
+        match self.recent.iter().rev().position(|&recent| recent == id) {
+            Some(0) => '+',
+            Some(1) => '-',
+            _ => ' ',
+        }
+    }
+
+    /// Resolve a job spec such as `%2`, `%+`, `%-`, `%vim` or `%?make` to a job number;
+    /// without a spec the current job is meant
+    pub fn resolve(&self, spec: Option<&str>) -> Result<usize, String> {
+        let no_such_job = || format!("{}: no such job", spec.unwrap_or("current"));
+        let spec_text = spec.map(|spec| spec.strip_prefix('%').unwrap_or(spec));
+
+        match spec_text {
+            None | Some("" | "%" | "+") => self.recent.last().copied().ok_or_else(no_such_job),
+            Some("-") => {
+                let previous = self.recent.len().checked_sub(2);
+                previous.map(|i| self.recent[i]).ok_or_else(no_such_job)
+            }
+            Some(number) if number.bytes().all(|b| b.is_ascii_digit()) => number
+                .parse()
+                .ok()
+                .filter(|&id| self.get(id).is_some())
+                .ok_or_else(no_such_job),
+            Some(text) => {
+                let matching = self
+                    .jobs
+                    .iter()
+                    .filter(|job| match text.strip_prefix('?') {
+                        Some(part) => job.command.contains(part),
+                        None => job.command.starts_with(text),
+                    })
+                    .map(|job| job.id)
+                    .collect::<Vec<_>>();
+
+                match matching[..] {
+                    [id] => Ok(id),
+                    [] => Err(no_such_job()),
+                    _ => Err(format!("{}: ambiguous job spec", spec.unwrap())),
+                }
+            }
+        }
+    }
+
+    /// Wait until a job finishes or stops, removing it once it has finished
+    pub fn wait_for(&mut self, id: usize) -> io::Result<ProcessState> {
+        let Some(job) = self.get_mut(id) else {
+            return Ok(ProcessState::Exited(127));
+        };
+        job.update(Wait::UntilStopped)?;
+
+        let state = job.state();
+        match state.is_finished() {
+            true => self.remove(id),
+            false => {
+                self.touch(id);
+                None
+            }
+        };
+        Ok(state)
+    }
+
+    /// Collect status changes of every job without blocking
+    pub fn update(&mut self) -> io::Result<()> {
+        self.jobs
+            .iter_mut()
+            .try_for_each(|job| job.update(Wait::NoHang))
+    }
+
+    /// Take the jobs that have finished since they were last checked, for reporting
+    pub fn take_finished(&mut self) -> Vec<(char, Job)> {
+        let finished = self
+            .jobs
+            .iter()
+            .filter(|job| job.is_finished())
+            .map(|job| (self.marker(job.id), job.id))
+            .collect::<Vec<_>>();
+
+        finished
+            .into_iter()
+            .filter_map(|(marker, id)| Some((marker, self.remove(id)?)))
+            .collect()
+    }
+}
+
+/// Describe a job's state the way `jobs` lists it
+pub fn describe(state: ProcessState) -> String {
+    match state {
+        ProcessState::Running => "Running".to_string(),
+        ProcessState::Stopped => "Stopped".to_string(),
+        ProcessState::Exited(0) => "Done".to_string(),
+        ProcessState::Exited(status) => format!("Exit {status}"),
+        ProcessState::Signaled(signal) => signal_description(signal),
+    }
+}
+
+/// The system's description of a signal, such as "Terminated" for SIGTERM
+fn signal_description(signal: i32) -> String {
+    let description = unsafe { libc::strsignal(signal) };
+    if description.is_null() {
+        return format!("Signal {signal}");
+    }
+    unsafe { CStr::from_ptr(description) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Format a line of `jobs` output, or a notice about a job that changed state
+pub fn job_line(job: &Job, marker: char, with_pid: bool) -> String {
+    let state = job.state();
+    let pid = match with_pid {
+        true => format!("{} ", job.pid()),
+        false => " ".to_string(),
+    };
+    let background = if state == ProcessState::Running {
+        " &"
+    } else {
+        ""
+    };
+
+    format!(
+        "[{}]{marker} {pid}{:<24}{}{background}",
+        job.id,
+        describe(state),
+        job.command
+    )
+}
+
+/// The controlling terminal of a shell with job control, handed to foreground jobs
+#[derive(Clone, Copy)]
+pub struct Terminal {
+    fd: RawFd,
+    /// Modes commands are started with, restored when the shell takes the terminal back
+    modes: libc::termios,
+}
+
+impl Terminal {
+    /// Take control of the terminal on standard input, if there is one, by putting the
+    /// shell in a process group of its own in the terminal's foreground and ignoring the
+    /// signals meant for foreground jobs
+    pub fn acquire() -> Option<Self> {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+            return None;
+        }
+
+        unsafe {
+            let fd = libc::fcntl(libc::STDIN_FILENO, libc::F_DUPFD_CLOEXEC, 10);
+            if fd == -1 {
+                return None;
+            }
+
+            signals::ignore_interactive();
+            // A session leader is already a group leader and cannot move
+            libc::setpgid(0, 0);
+            libc::tcsetpgrp(fd, libc::getpgrp());
+
+            let mut modes = std::mem::zeroed();
+            libc::tcgetattr(fd, &mut modes);
+            Some(Self { fd, modes })
+        }
+    }
+
+    /// Make a process group the terminal's foreground group
+    pub fn give(&self, pgid: i32) {
+        unsafe { libc::tcsetpgrp(self.fd, pgid) };
+    }
+
+    /// Take the terminal back after a foreground job finished or stopped, undoing any
+    /// mode changes the job made
+    pub fn reclaim(&self) {
+        unsafe {
+            libc::tcsetpgrp(self.fd, libc::getpgrp());
+            libc::tcsetattr(self.fd, libc::TCSADRAIN, &self.modes);
+        }
+    }
+}
+
+/// Set up a command to run as a process of a job: with a terminal, in process group
+/// `pgid` or in a new group it leads, taking the terminal itself if it runs in the
+/// foreground so it never has to wait for the shell to hand it over
+pub fn prepare_process(
+    command: &mut Command,
+    terminal: Option<Terminal>,
+    pgid: Option<i32>,
+    foreground: bool,
+) {
+    let terminal_fd = terminal.map(|terminal| terminal.fd);
+    if terminal_fd.is_some() {
+        command.process_group(pgid.unwrap_or(0));
+    }
+
+    unsafe {
+        command.pre_exec(move || {
+            if foreground && let Some(fd) = terminal_fd {
+                libc::tcsetpgrp(fd, libc::getpgrp());
+            }
+            signals::restore_defaults();
+            Ok(())
+        });
+    }
+}
+
+/// Wait for the processes of a foreground job until they finish or stop, then take the
+/// terminal back. A job that stopped is added to the job table. Returns the status of
+/// each process
+pub fn wait_foreground(
+    shell: &mut Shell,
+    processes: Vec<Process>,
+    pgid: Option<i32>,
+    command: String,
+) -> io::Result<Vec<i32>> {
+    let mut job = Job {
+        id: 0,
+        processes,
+        pgid,
+        command,
+    };
+    // Without job control nothing can take over when a job stops, so it is waited for
+    let waited = match shell.terminal {
+        Some(_) => job.update(Wait::UntilStopped),
+        None => job.update(Wait::UntilFinished),
+    };
+    if let Some(terminal) = &shell.terminal {
+        terminal.reclaim();
+    }
+    waited?;
+
+    let statuses = job
+        .processes
+        .iter()
+        .map(|process| process.state.status())
+        .collect();
+
+    // The terminal echoed `^C` where the job was interrupted; the prompt goes on a new
+    // line, and like the job, the rest of the command line is abandoned
+    let interrupted = ProcessState::Signaled(libc::SIGINT);
+    if shell.terminal.is_some() && job.processes.iter().any(|p| p.state == interrupted) {
+        eprintln!();
+        shell.flow = Some(Flow::Interrupt);
+    }
+
+    if job.state() == ProcessState::Stopped {
+        let id = shell.jobs.add(job.processes, job.pgid, job.command);
+        let job = shell.jobs.get(id).unwrap();
+        eprintln!("\n{}", job_line(job, shell.jobs.marker(id), false));
+    }
+
+    Ok(statuses)
+}
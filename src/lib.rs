@@ -1,7 +1,16 @@
+pub mod arithmetic;
 pub mod builtins;
 pub mod commands;
 pub mod executor;
+pub mod expansion;
 pub mod history;
 pub mod input;
+pub mod jobs;
+pub mod parser;
 pub mod redirection;
+pub mod script;
+pub mod shell;
+pub mod signals;
+pub mod traps;
 pub mod utils;
+pub mod variables;
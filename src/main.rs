@@ -1,12 +1,78 @@
 use std::collections::HashSet;
 use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::os::fd::AsFd;
 use std::os::unix::fs::MetadataExt;
+use std::process::exit;
 
 use codecrafters_shell::builtins::VALID_COMMANDS_BUILTIN;
 use codecrafters_shell::history::get_history;
 use codecrafters_shell::input::input_loop;
+use codecrafters_shell::jobs::Terminal;
+use codecrafters_shell::script::run_script;
+use codecrafters_shell::shell::Shell;
+use codecrafters_shell::utils::io_error_message;
 
-fn main() -> std::io::Result<()> {
+const USAGE: &str = "usage: rsh [-c command [name [argument ...]] | script [argument ...]]";
+
+fn main() -> io::Result<()> {
+    let mut args = env::args();
+    let name = args.next().unwrap_or_else(|| "rsh".to_string());
+    let args = args.collect::<Vec<_>>();
+
+    let mut shell = Shell::new(Vec::new());
+    shell.name = name;
+
+    let status = match args.split_first() {
+        Some((option, rest)) if option == "-c" => {
+            let Some((command, rest)) = rest.split_first() else {
+                eprintln!("rsh: -c: option requires an argument");
+                exit(2);
+            };
+            // As with `sh -c`, the first argument after the command becomes `$0`
+            if let Some((name, arguments)) = rest.split_first() {
+                shell.name = name.clone();
+                shell.positional = arguments.to_vec();
+            }
+            run_script(command.as_bytes(), Some("-c"), &mut shell)?
+        }
+        Some((option, _)) if option.starts_with('-') => {
+            eprintln!("rsh: {option}: invalid option");
+            eprintln!("{USAGE}");
+            exit(2);
+        }
+        Some((path, arguments)) => {
+            let script = match File::open(path) {
+                Ok(script) => script,
+                Err(err) => {
+                    eprintln!("rsh: {path}: {}", io_error_message(&err));
+                    exit(127);
+                }
+            };
+            if script.metadata()?.is_dir() {
+                eprintln!("rsh: {path}: is a directory");
+                exit(126);
+            }
+            shell.name = path.clone();
+            shell.positional = arguments.to_vec();
+            run_script(BufReader::new(script), Some(path), &mut shell)?
+        }
+        // Commands piped in are read through a duplicate of standard input, which shares
+        // its position, so that commands they run can read the lines after them
+        None if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 => {
+            let stdin = File::from(io::stdin().as_fd().try_clone_to_owned()?);
+            run_script(stdin, None, &mut shell)?
+        }
+        None => return interactive(shell),
+    };
+
+    io::stdout().flush()?;
+    exit(status);
+}
+
+/// Read commands from the terminal with line editing, completion and job control
+fn interactive(mut shell: Shell) -> io::Result<()> {
     let mut cmds = Vec::<String>::new();
     let mut local_history = Vec::<String>::new();
 
@@ -44,23 +110,28 @@ fn main() -> std::io::Result<()> {
 
     // Add built-in commands to the list
     cmds.extend(VALID_COMMANDS_BUILTIN.iter().map(|s| s.to_string()));
-    
+
     // Deduplicate commands
     let set_cmds = cmds.into_iter().collect::<HashSet<String>>();
     let cmds = set_cmds.into_iter().collect::<Vec<_>>();
 
     // Start the input loop
-    input_loop(&cmds, &mut local_history)
+    shell.history = local_history;
+    shell.interactive = true;
+    shell.terminal = Terminal::acquire();
+    input_loop(&cmds, &mut shell)
 }
 
 #[cfg(test)]
 mod tests {
     use codecrafters_shell::commands::pipeline_handler;
+    use codecrafters_shell::parser::parse;
+    use codecrafters_shell::shell::Shell;
 
     #[test]
     fn testing() -> anyhow::Result<()> {
-        let command = "history -r";
-        let _pipelined = pipeline_handler(command);
+        let command = parse("history -r")?;
+        let _pipelined = pipeline_handler(&command.items[0].and_or.first, &mut Shell::default());
 
         Ok(())
     }
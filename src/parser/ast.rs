@@ -0,0 +1,368 @@
+use std::fmt;
+
+use crate::redirection::{REDIRECTIONS, RedirectionKind};
+
+/// A single shell word, kept in its source form (quotes included) until expansion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word(pub String);
+
+/// A `NAME=value` assignment preceding a simple command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+/// A redirection attached to a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirection {
+    /// Descriptor being redirected, the operator's default unless a number preceded it
+    pub fd: u32,
+    pub kind: RedirectionKind,
+    /// File name or descriptor, or for a here-document its body
+    pub target: Word,
+    /// Delimiter of a here-document, without its quotes
+    pub delimiter: Option<String>,
+}
+
+/// A command name with its arguments, assignments and redirections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirections: Vec<Redirection>,
+}
+
+/// Compound commands built from nested lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompoundCommand {
+    BraceGroup(List),
+    Subshell(List),
+    /// `(( expression ))`, succeeding when the expression is non-zero
+    Arithmetic(String),
+    If {
+        conditionals: Vec<(List, List)>,
+        else_branch: Option<List>,
+    },
+    While {
+        condition: List,
+        body: List,
+    },
+    Until {
+        condition: List,
+        body: List,
+    },
+    For {
+        variable: String,
+        words: Option<Vec<Word>>,
+        body: List,
+    },
+    /// `for (( init; condition; step ))`, with each expression possibly empty
+    ArithmeticFor {
+        init: String,
+        condition: String,
+        step: String,
+        body: List,
+    },
+    Case {
+        word: Word,
+        items: Vec<CaseItem>,
+    },
+}
+
+/// A single `pattern | pattern) list ;;` arm of a case statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseItem {
+    pub patterns: Vec<Word>,
+    pub body: List,
+    pub terminator: CaseTerminator,
+}
+
+/// What happens after a case arm's list has run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseTerminator {
+    /// `;;` ends the case statement
+    Break,
+    /// `;&` runs the next arm's list without testing its patterns
+    FallThrough,
+    /// `;;&` goes on testing the patterns of the following arms
+    Continue,
+}
+
+/// A `name() compound-command` function definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub body: CompoundCommand,
+    /// Redirections applied each time the function is called
+    pub redirections: Vec<Redirection>,
+}
+
+/// Any command that can appear as a pipeline stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirection>),
+    Function(FunctionDefinition),
+}
+
+/// Commands connected with `|`, optionally negated with `!`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    pub negated: bool,
+    pub commands: Vec<Command>,
+}
+
+/// Operators joining pipelines in an AND-OR list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AndOrOperator {
+    And,
+    Or,
+}
+
+/// Pipelines joined by `&&` and `||`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(AndOrOperator, Pipeline)>,
+}
+
+/// An AND-OR list terminated by `;`, `&` or a newline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    pub and_or: AndOr,
+    pub background: bool,
+}
+
+/// A sequence of AND-OR lists, the unit the parser produces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct List {
+    pub items: Vec<ListItem>,
+}
+
+// Commands are displayed on one line in a normalised form of their source, as `jobs`
+// shows them; only here-document bodies are put on the lines after their command
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for Redirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.fd != self.kind.default_fd() {
+            write!(f, "{}", self.fd)?;
+        }
+
+        match self.kind {
+            // The body follows the command line, written by the list the command is in
+            RedirectionKind::HereDoc { expand } => {
+                let delimiter = self.delimiter.as_deref().unwrap_or_default();
+                match expand {
+                    true => write!(f, "<<{delimiter}"),
+                    false => write!(f, "<<'{delimiter}'"),
+                }
+            }
+            kind => {
+                let (operator, _) = REDIRECTIONS.iter().find(|(_, k)| *k == kind).unwrap();
+                write!(f, "{operator}{}", self.target)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let assignments = self
+            .assignments
+            .iter()
+            .map(|assignment| format!("{}={}", assignment.name, assignment.value));
+        let words = self.words.iter().map(Word::to_string);
+        let redirections = self.redirections.iter().map(Redirection::to_string);
+
+        let parts = assignments
+            .chain(words)
+            .chain(redirections)
+            .collect::<Vec<_>>();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+impl fmt::Display for CompoundCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompoundCommand::BraceGroup(list) => write!(f, "{{ {} }}", terminated(list)),
+            CompoundCommand::Subshell(list) => write!(f, "( {list} )"),
+            CompoundCommand::Arithmetic(expression) => write!(f, "(({expression}))"),
+            CompoundCommand::If {
+                conditionals,
+                else_branch,
+            } => {
+                for (i, (condition, body)) in conditionals.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { " elif" };
+                    let (condition, body) = (terminated(condition), terminated(body));
+                    write!(f, "{keyword} {condition} then {body}")?;
+                }
+                if let Some(else_branch) = else_branch {
+                    write!(f, " else {}", terminated(else_branch))?;
+                }
+                write!(f, " fi")
+            }
+            CompoundCommand::While { condition, body } => {
+                let (condition, body) = (terminated(condition), terminated(body));
+                write!(f, "while {condition} do {body} done")
+            }
+            CompoundCommand::Until { condition, body } => {
+                let (condition, body) = (terminated(condition), terminated(body));
+                write!(f, "until {condition} do {body} done")
+            }
+            CompoundCommand::For {
+                variable,
+                words,
+                body,
+            } => {
+                write!(f, "for {variable}")?;
+                if let Some(words) = words {
+                    write!(f, " in")?;
+                    for word in words {
+                        write!(f, " {word}")?;
+                    }
+                }
+                write!(f, "; do {} done", terminated(body))
+            }
+            CompoundCommand::ArithmeticFor {
+                init,
+                condition,
+                step,
+                body,
+            } => write!(
+                f,
+                "for (({init}; {condition}; {step})); do {} done",
+                terminated(body)
+            ),
+            CompoundCommand::Case { word, items } => {
+                write!(f, "case {word} in")?;
+                for item in items {
+                    let patterns = item.patterns.iter().map(Word::to_string);
+                    let patterns = patterns.collect::<Vec<_>>().join(" | ");
+                    let terminator = match item.terminator {
+                        CaseTerminator::Break => ";;",
+                        CaseTerminator::FallThrough => ";&",
+                        CaseTerminator::Continue => ";;&",
+                    };
+                    write!(f, " {patterns}) {}{terminator}", item.body)?;
+                }
+                write!(f, " esac")
+            }
+        }
+    }
+}
+
+impl fmt::Display for FunctionDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} () {}", self.name, self.body)?;
+        for redirection in &self.redirections {
+            write!(f, " {redirection}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Simple(simple) => write!(f, "{simple}"),
+            Command::Compound(compound, redirections) => {
+                write!(f, "{compound}")?;
+                for redirection in redirections {
+                    write!(f, " {redirection}")?;
+                }
+                Ok(())
+            }
+            Command::Function(function) => write!(f, "{function}"),
+        }
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            write!(f, "! ")?;
+        }
+        let commands = self.commands.iter().map(Command::to_string);
+        write!(f, "{}", commands.collect::<Vec<_>>().join(" | "))
+    }
+}
+
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (op, pipeline) in &self.rest {
+            let op = match op {
+                AndOrOperator::And => "&&",
+                AndOrOperator::Or => "||",
+            };
+            write!(f, " {op} {pipeline}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // After here-document bodies a new line has already started
+        let mut line_ended = false;
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 && !line_ended {
+                let separator = if self.items[i - 1].background {
+                    " "
+                } else {
+                    "; "
+                };
+                write!(f, "{separator}")?;
+            }
+            write!(f, "{}", item.and_or)?;
+            if item.background {
+                write!(f, " &")?;
+            }
+
+            let mut heredocs = item.and_or.heredocs().peekable();
+            line_ended = heredocs.peek().is_some();
+            if line_ended {
+                writeln!(f)?;
+            }
+            for heredoc in heredocs {
+                let delimiter = heredoc.delimiter.as_deref().unwrap_or_default();
+                writeln!(f, "{}{delimiter}", heredoc.target)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AndOr {
+    /// Here-documents of the commands in the AND-OR list, not counting those nested in
+    /// compound commands, whose bodies follow the list's line
+    fn heredocs(&self) -> impl Iterator<Item = &Redirection> {
+        let pipelines = std::iter::once(&self.first).chain(self.rest.iter().map(|(_, p)| p));
+        pipelines
+            .flat_map(|pipeline| &pipeline.commands)
+            .flat_map(|command| match command {
+                Command::Simple(simple) => &simple.redirections,
+                Command::Compound(_, redirections) => redirections,
+                Command::Function(function) => &function.redirections,
+            })
+            .filter(|redirection| matches!(redirection.kind, RedirectionKind::HereDoc { .. }))
+    }
+}
+
+/// A list as it is written before a closing keyword, ended by `;` unless it already ends
+/// with a here-document body
+fn terminated(list: &List) -> String {
+    let list = list.to_string();
+    match list.ends_with('\n') {
+        true => list,
+        false => list + ";",
+    }
+}
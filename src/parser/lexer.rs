@@ -0,0 +1,456 @@
+use std::fmt;
+
+use super::ParseError;
+
+/// Control and redirection operators recognised by the lexer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Pipe,
+    AndIf,
+    OrIf,
+    Amp,
+    Semi,
+    DSemi,
+    /// `;&`, falling through to the next case arm
+    SemiAnd,
+    /// `;;&`, testing the following case arms
+    DSemiAnd,
+    LParen,
+    RParen,
+    Less,
+    Great,
+    DLess,
+    TLess,
+    DGreat,
+    DLessDash,
+    LessAnd,
+    GreatAnd,
+    LessGreat,
+    Clobber,
+    AndGreat,
+    AndDGreat,
+}
+
+/// Operators with their spelling, longest first so the lexer can match greedily
+const OPERATORS: &[(&str, Operator)] = &[
+    ("<<<", Operator::TLess),
+    (";;&", Operator::DSemiAnd),
+    ("&>>", Operator::AndDGreat),
+    ("<<-", Operator::DLessDash),
+    ("&&", Operator::AndIf),
+    ("||", Operator::OrIf),
+    (";;", Operator::DSemi),
+    (";&", Operator::SemiAnd),
+    ("<<", Operator::DLess),
+    (">>", Operator::DGreat),
+    ("<&", Operator::LessAnd),
+    (">&", Operator::GreatAnd),
+    ("<>", Operator::LessGreat),
+    (">|", Operator::Clobber),
+    ("&>", Operator::AndGreat),
+    ("|", Operator::Pipe),
+    ("&", Operator::Amp),
+    (";", Operator::Semi),
+    ("(", Operator::LParen),
+    (")", Operator::RParen),
+    ("<", Operator::Less),
+    (">", Operator::Great),
+];
+
+impl Operator {
+    /// The operator as it is written in shell source
+    pub fn as_str(self) -> &'static str {
+        OPERATORS
+            .iter()
+            .find(|(_, op)| *op == self)
+            .map(|(s, _)| *s)
+            .unwrap()
+    }
+
+    /// Whether the operator introduces a redirection
+    pub fn is_redirection(self) -> bool {
+        matches!(
+            self,
+            Operator::Less
+                | Operator::Great
+                | Operator::DLess
+                | Operator::TLess
+                | Operator::DGreat
+                | Operator::DLessDash
+                | Operator::LessAnd
+                | Operator::GreatAnd
+                | Operator::LessGreat
+                | Operator::Clobber
+                | Operator::AndGreat
+                | Operator::AndDGreat
+        )
+    }
+}
+
+/// A lexical token of shell input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// A word in source form, quotes and expansions left intact
+    Word(String),
+    /// A file descriptor number directly preceding a redirection operator
+    IoNumber(u32),
+    Operator(Operator),
+    /// The expression inside an `(( ... ))` arithmetic command
+    Arithmetic(String),
+    /// The delimiter word of a here-document, with the body read from the following lines
+    HereDoc {
+        delimiter: String,
+        body: String,
+        /// Whether the delimiter was quoted, which disables expansion of the body
+        quoted: bool,
+    },
+    Newline,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{word}"),
+            Token::IoNumber(fd) => write!(f, "{fd}"),
+            Token::Operator(op) => write!(f, "{}", op.as_str()),
+            Token::Arithmetic(expression) => write!(f, "(({expression}))"),
+            Token::HereDoc { delimiter, .. } => write!(f, "{delimiter}"),
+            Token::Newline => write!(f, "newline"),
+        }
+    }
+}
+
+/// Split shell input into tokens
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    Lexer {
+        chars: input.chars().collect(),
+        pos: 0,
+        heredoc_operator: None,
+        pending_heredocs: Vec::new(),
+    }
+    .tokenize()
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    /// Set after `<<` or `<<-`, telling whether tabs are stripped from the coming here-document
+    heredoc_operator: Option<bool>,
+    /// Here-document tokens whose bodies start after the next newline, with their tab stripping
+    pending_heredocs: Vec<(usize, bool)>,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>, ParseError> {
+        let mut tokens = Vec::new();
+
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' => self.pos += 1,
+                '\\' if self.peek_at(1) == Some('\n') => self.pos += 2,
+                '\n' => {
+                    self.pos += 1;
+                    tokens.push(Token::Newline);
+                    self.heredoc_bodies(&mut tokens)?;
+                }
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                '(' if self.peek_at(1) == Some('(')
+                    && let Some(token) = self.arithmetic() =>
+                {
+                    tokens.push(token)
+                }
+                _ => match self.operator() {
+                    Some(op) => {
+                        if matches!(op, Operator::DLess | Operator::DLessDash) {
+                            self.heredoc_operator = Some(op == Operator::DLessDash);
+                        }
+                        tokens.push(Token::Operator(op));
+                    }
+                    None => {
+                        let token = self.word()?;
+                        match (self.heredoc_operator.take(), token) {
+                            (Some(strip_tabs), Token::Word(word)) => {
+                                self.pending_heredocs.push((tokens.len(), strip_tabs));
+                                tokens.push(heredoc_delimiter(&word));
+                            }
+                            (_, token) => tokens.push(token),
+                        }
+                    }
+                },
+            }
+        }
+
+        // A here-document whose body has not been typed yet leaves the input incomplete
+        if !self.pending_heredocs.is_empty() {
+            return Err(ParseError::UnexpectedEof);
+        }
+
+        Ok(tokens)
+    }
+
+    /// Read the bodies of here-documents introduced on the line that just ended
+    fn heredoc_bodies(&mut self, tokens: &mut [Token]) -> Result<(), ParseError> {
+        for (index, strip_tabs) in std::mem::take(&mut self.pending_heredocs) {
+            let Token::HereDoc {
+                delimiter, body, ..
+            } = &mut tokens[index]
+            else {
+                unreachable!("pending here-documents point at here-document tokens");
+            };
+
+            loop {
+                if self.peek().is_none() {
+                    return Err(ParseError::UnexpectedEof);
+                }
+
+                let mut line = String::new();
+                while let Some(c) = self.bump() {
+                    if c == '\n' {
+                        break;
+                    }
+                    line.push(c);
+                }
+
+                let line = if strip_tabs {
+                    line.trim_start_matches('\t')
+                } else {
+                    &line
+                };
+                if line == delimiter {
+                    break;
+                }
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consume an operator at the current position, if there is one
+    fn operator(&mut self) -> Option<Operator> {
+        let (spelling, op) = OPERATORS.iter().find(|(spelling, _)| {
+            spelling
+                .chars()
+                .enumerate()
+                .all(|(i, c)| self.peek_at(i) == Some(c))
+        })?;
+
+        self.pos += spelling.len();
+        Some(*op)
+    }
+
+    /// Consume `(( expression ))`, or nothing if the parentheses turn out to open nested subshells
+    fn arithmetic(&mut self) -> Option<Token> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let mut inner = String::new();
+        if self.balanced(&mut inner, '(', ')').is_ok() && self.peek() == Some(')') {
+            self.pos += 1;
+            let expression = inner[1..inner.len() - 1].to_string();
+            return Some(Token::Arithmetic(expression));
+        }
+
+        self.pos = start;
+        None
+    }
+
+    /// Consume a word, keeping quotes and expansions in their source form
+    fn word(&mut self) -> Result<Token, ParseError> {
+        let mut word = String::new();
+
+        while let Some(c) = self.peek() {
+            match c {
+                // An extglob group such as `!(*.rs)` stays part of the word
+                '(' if word.ends_with(['?', '*', '+', '@', '!']) => {
+                    self.balanced(&mut word, '(', ')')?
+                }
+                ' ' | '\t' | '\n' | '|' | '&' | ';' | '<' | '>' | '(' | ')' => break,
+                '\\' => {
+                    self.pos += 1;
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(escaped) => {
+                            word.push('\\');
+                            word.push(escaped);
+                        }
+                        None => word.push('\\'),
+                    }
+                }
+                '\'' => self.single_quoted(&mut word)?,
+                '"' => self.double_quoted(&mut word)?,
+                '`' => self.backquoted(&mut word)?,
+                '$' => self.dollar(&mut word)?,
+                _ => {
+                    self.pos += 1;
+                    word.push(c);
+                }
+            }
+        }
+
+        if matches!(self.peek(), Some('<' | '>'))
+            && word.chars().all(|c| c.is_ascii_digit())
+            && let Ok(fd) = word.parse::<u32>()
+        {
+            return Ok(Token::IoNumber(fd));
+        }
+
+        Ok(Token::Word(word))
+    }
+
+    fn single_quoted(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push(self.bump().unwrap());
+
+        loop {
+            match self.bump() {
+                Some('\'') => {
+                    word.push('\'');
+                    return Ok(());
+                }
+                Some(c) => word.push(c),
+                None => return Err(ParseError::UnexpectedEof),
+            }
+        }
+    }
+
+    fn double_quoted(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push(self.bump().unwrap());
+
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    word.push('"');
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(escaped) => {
+                            word.push('\\');
+                            word.push(escaped);
+                        }
+                        None => return Err(ParseError::UnexpectedEof),
+                    }
+                }
+                Some('`') => self.backquoted(word)?,
+                Some('$') => self.dollar(word)?,
+                Some(c) => {
+                    self.pos += 1;
+                    word.push(c);
+                }
+                None => return Err(ParseError::UnexpectedEof),
+            }
+        }
+    }
+
+    fn backquoted(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push(self.bump().unwrap());
+
+        loop {
+            match self.bump() {
+                Some('`') => {
+                    word.push('`');
+                    return Ok(());
+                }
+                Some('\\') => {
+                    word.push('\\');
+                    match self.bump() {
+                        Some(escaped) => word.push(escaped),
+                        None => return Err(ParseError::UnexpectedEof),
+                    }
+                }
+                Some(c) => word.push(c),
+                None => return Err(ParseError::UnexpectedEof),
+            }
+        }
+    }
+
+    /// Consume `$`, along with a whole `$(...)`, `$((...))` or `${...}` if one follows
+    fn dollar(&mut self, word: &mut String) -> Result<(), ParseError> {
+        word.push(self.bump().unwrap());
+
+        match self.peek() {
+            Some('(') => self.balanced(word, '(', ')'),
+            Some('{') => self.balanced(word, '{', '}'),
+            _ => Ok(()),
+        }
+    }
+
+    /// Consume text up to the bracket matching the one at the current position
+    fn balanced(&mut self, word: &mut String, open: char, close: char) -> Result<(), ParseError> {
+        word.push(self.bump().unwrap());
+        let mut depth = 1;
+
+        loop {
+            match self.peek() {
+                Some('\\') => {
+                    self.pos += 1;
+                    word.push('\\');
+                    match self.bump() {
+                        Some(escaped) => word.push(escaped),
+                        None => return Err(ParseError::UnexpectedEof),
+                    }
+                }
+                Some('\'') => self.single_quoted(word)?,
+                Some('"') => self.double_quoted(word)?,
+                Some('`') => self.backquoted(word)?,
+                Some('$') => self.dollar(word)?,
+                Some(c) => {
+                    self.pos += 1;
+                    word.push(c);
+
+                    if c == open {
+                        depth += 1;
+                    } else if c == close {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(());
+                        }
+                    }
+                }
+                None => return Err(ParseError::UnexpectedEof),
+            }
+        }
+    }
+}
+
+/// Here-document token for a delimiter word, removing its quotes
+fn heredoc_delimiter(word: &str) -> Token {
+    let mut delimiter = String::new();
+    let mut chars = word.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {}
+            '\\' => delimiter.extend(chars.next()),
+            _ => delimiter.push(c),
+        }
+    }
+
+    Token::HereDoc {
+        delimiter,
+        body: String::new(),
+        quoted: word.contains(['\'', '"', '\\']),
+    }
+}
@@ -0,0 +1,699 @@
+pub mod ast;
+mod lexer;
+
+use thiserror::Error;
+
+use crate::redirection::{REDIRECTIONS, RedirectionKind};
+use crate::utils::is_valid_name;
+use ast::*;
+use lexer::{Operator, Token};
+
+/// Errors reported while parsing shell input
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("syntax error: unexpected end of file")]
+    UnexpectedEof,
+    #[error("syntax error near unexpected token `{0}'")]
+    UnexpectedToken(String),
+    #[error("{0}: unsupported redirection")]
+    UnsupportedRedirection(String),
+}
+
+/// Reserved words that end a compound list
+const LIST_TERMINATORS: &[&str] = &["then", "else", "elif", "fi", "do", "done", "esac", "}"];
+
+/// Whether the input stops in the middle of a command, so more lines should be read
+pub fn is_incomplete(input: &str) -> bool {
+    parse(input) == Err(ParseError::UnexpectedEof)
+}
+
+/// Parse shell input into a command list
+pub fn parse(input: &str) -> Result<List, ParseError> {
+    let mut parser = Parser {
+        tokens: lexer::tokenize(input)?,
+        pos: 0,
+    };
+
+    let list = parser.compound_list()?;
+    match parser.peek() {
+        Some(_) => Err(parser.unexpected()),
+        None => Ok(list),
+    }
+}
+
+/// Recursive-descent parser over the token stream
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError::UnexpectedToken(token.to_string()),
+            None => ParseError::UnexpectedEof,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn eat_operator(&mut self, op: Operator) -> bool {
+        let found = self.peek() == Some(&Token::Operator(op));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_operator(&mut self, op: Operator) -> Result<(), ParseError> {
+        if self.eat_operator(op) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    fn at_list_end(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(Token::Operator(op)) => matches!(
+                op,
+                Operator::RParen | Operator::DSemi | Operator::SemiAnd | Operator::DSemiAnd
+            ),
+            Some(Token::Word(word)) => LIST_TERMINATORS.contains(&word.as_str()),
+            _ => false,
+        }
+    }
+
+    /// Parse AND-OR lists separated by `;`, `&` or newlines
+    fn compound_list(&mut self) -> Result<List, ParseError> {
+        let mut items = Vec::new();
+        self.skip_newlines();
+
+        while !self.at_list_end() {
+            let and_or = self.and_or()?;
+            let background = self.eat_operator(Operator::Amp);
+            let separated = background
+                || self.eat_operator(Operator::Semi)
+                || self.peek() == Some(&Token::Newline);
+
+            items.push(ListItem { and_or, background });
+            self.skip_newlines();
+
+            if !separated {
+                break;
+            }
+        }
+
+        Ok(List { items })
+    }
+
+    /// Parse a compound list that must contain at least one command
+    fn nonempty_list(&mut self) -> Result<List, ParseError> {
+        let list = self.compound_list()?;
+        if list.items.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+
+        loop {
+            let op = if self.eat_operator(Operator::AndIf) {
+                AndOrOperator::And
+            } else if self.eat_operator(Operator::OrIf) {
+                AndOrOperator::Or
+            } else {
+                break;
+            };
+
+            self.skip_newlines();
+            rest.push((op, self.pipeline()?));
+        }
+
+        Ok(AndOr { first, rest })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let negated = self.eat_keyword("!");
+        let mut commands = vec![self.command()?];
+
+        while self.eat_operator(Operator::Pipe) {
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+
+        Ok(Pipeline { negated, commands })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        let compound = match self.peek() {
+            Some(Token::Operator(Operator::LParen)) => {
+                self.pos += 1;
+                let body = self.nonempty_list()?;
+                self.expect_operator(Operator::RParen)?;
+                CompoundCommand::Subshell(body)
+            }
+            Some(Token::Arithmetic(expression)) => {
+                let expression = expression.clone();
+                self.pos += 1;
+                CompoundCommand::Arithmetic(expression)
+            }
+            Some(Token::Word(word)) => match word.as_str() {
+                "{" => {
+                    self.pos += 1;
+                    let body = self.nonempty_list()?;
+                    self.expect_keyword("}")?;
+                    CompoundCommand::BraceGroup(body)
+                }
+                "if" => self.if_clause()?,
+                "while" => {
+                    self.pos += 1;
+                    let condition = self.nonempty_list()?;
+                    let body = self.do_group()?;
+                    CompoundCommand::While { condition, body }
+                }
+                "until" => {
+                    self.pos += 1;
+                    let condition = self.nonempty_list()?;
+                    let body = self.do_group()?;
+                    CompoundCommand::Until { condition, body }
+                }
+                "for" => self.for_clause()?,
+                "case" => self.case_clause()?,
+                "function" => return self.function_definition(),
+                _ if self.at_function_definition() => return self.function_definition(),
+                _ => return Ok(Command::Simple(self.simple_command()?)),
+            },
+            _ => return Ok(Command::Simple(self.simple_command()?)),
+        };
+
+        let mut redirections = Vec::new();
+        while self.at_redirection() {
+            redirections.push(self.redirection()?);
+        }
+
+        Ok(Command::Compound(compound, redirections))
+    }
+
+    fn if_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_keyword("if")?;
+        let mut conditionals = Vec::new();
+        let mut else_branch = None;
+
+        loop {
+            let condition = self.nonempty_list()?;
+            self.expect_keyword("then")?;
+            let body = self.nonempty_list()?;
+            conditionals.push((condition, body));
+
+            if self.eat_keyword("elif") {
+                continue;
+            }
+            if self.eat_keyword("else") {
+                else_branch = Some(self.nonempty_list()?);
+            }
+            self.expect_keyword("fi")?;
+            break;
+        }
+
+        Ok(CompoundCommand::If {
+            conditionals,
+            else_branch,
+        })
+    }
+
+    fn do_group(&mut self) -> Result<List, ParseError> {
+        self.expect_keyword("do")?;
+        let body = self.nonempty_list()?;
+        self.expect_keyword("done")?;
+        Ok(body)
+    }
+
+    fn for_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_keyword("for")?;
+
+        let variable = match self.advance() {
+            Some(Token::Arithmetic(expressions)) => {
+                return self.arithmetic_for(expressions);
+            }
+            Some(Token::Word(name)) if is_valid_name(&name) => name,
+            Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            None => return Err(ParseError::UnexpectedEof),
+        };
+
+        self.skip_newlines();
+        let words = if self.eat_keyword("in") {
+            let mut words = Vec::new();
+            while let Some(Token::Word(word)) = self.peek() {
+                words.push(Word(word.clone()));
+                self.pos += 1;
+            }
+            if !self.eat_operator(Operator::Semi) && self.peek() != Some(&Token::Newline) {
+                return Err(self.unexpected());
+            }
+            Some(words)
+        } else {
+            self.eat_operator(Operator::Semi);
+            None
+        };
+
+        self.skip_newlines();
+        let body = self.do_group()?;
+
+        Ok(CompoundCommand::For {
+            variable,
+            words,
+            body,
+        })
+    }
+
+    /// Parse the rest of `for (( init; condition; step ))` once the expressions are read
+    fn arithmetic_for(&mut self, expressions: String) -> Result<CompoundCommand, ParseError> {
+        let [init, condition, step] = expressions
+            .split(';')
+            .map(|expression| expression.trim().to_string())
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| ParseError::UnexpectedToken(format!("(({expressions}))")))?;
+
+        self.eat_operator(Operator::Semi);
+        self.skip_newlines();
+        let body = self.do_group()?;
+
+        Ok(CompoundCommand::ArithmeticFor {
+            init,
+            condition,
+            step,
+            body,
+        })
+    }
+
+    fn case_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_keyword("case")?;
+
+        let word = match self.advance() {
+            Some(Token::Word(word)) => Word(word),
+            Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            None => return Err(ParseError::UnexpectedEof),
+        };
+
+        self.skip_newlines();
+        self.expect_keyword("in")?;
+        self.skip_newlines();
+
+        let mut items = Vec::new();
+        while !self.eat_keyword("esac") {
+            self.eat_operator(Operator::LParen);
+
+            let mut patterns = Vec::new();
+            loop {
+                match self.advance() {
+                    Some(Token::Word(pattern)) => patterns.push(Word(pattern)),
+                    Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+                    None => return Err(ParseError::UnexpectedEof),
+                }
+                if !self.eat_operator(Operator::Pipe) {
+                    break;
+                }
+            }
+            self.expect_operator(Operator::RParen)?;
+
+            let body = self.compound_list()?;
+            let terminator = if self.eat_operator(Operator::DSemi) {
+                CaseTerminator::Break
+            } else if self.eat_operator(Operator::SemiAnd) {
+                CaseTerminator::FallThrough
+            } else if self.eat_operator(Operator::DSemiAnd) {
+                CaseTerminator::Continue
+            } else {
+                items.push(CaseItem {
+                    patterns,
+                    body,
+                    terminator: CaseTerminator::Break,
+                });
+                self.expect_keyword("esac")?;
+                break;
+            };
+            items.push(CaseItem {
+                patterns,
+                body,
+                terminator,
+            });
+            self.skip_newlines();
+        }
+
+        Ok(CompoundCommand::Case { word, items })
+    }
+
+    /// Whether the input continues with `name()`
+    fn at_function_definition(&self) -> bool {
+        matches!(self.peek(), Some(Token::Word(name)) if is_function_name(name))
+            && self.tokens.get(self.pos + 1) == Some(&Token::Operator(Operator::LParen))
+            && self.tokens.get(self.pos + 2) == Some(&Token::Operator(Operator::RParen))
+    }
+
+    /// Parse `name() body` or `function name body`, where the body is a compound command
+    fn function_definition(&mut self) -> Result<Command, ParseError> {
+        let keyword = self.eat_keyword("function");
+
+        let name = match self.advance() {
+            Some(Token::Word(name)) if is_function_name(&name) => name,
+            Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            None => return Err(ParseError::UnexpectedEof),
+        };
+        // The parentheses are optional after the `function` keyword
+        if !keyword || self.peek() == Some(&Token::Operator(Operator::LParen)) {
+            self.expect_operator(Operator::LParen)?;
+            self.expect_operator(Operator::RParen)?;
+        }
+
+        self.skip_newlines();
+        let start = self.pos;
+        match self.command()? {
+            Command::Compound(body, redirections) => Ok(Command::Function(FunctionDefinition {
+                name,
+                body,
+                redirections,
+            })),
+            _ => {
+                self.pos = start;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    fn at_redirection(&self) -> bool {
+        match self.peek() {
+            Some(Token::IoNumber(_)) => true,
+            Some(Token::Operator(op)) => op.is_redirection(),
+            _ => false,
+        }
+    }
+
+    fn redirection(&mut self) -> Result<Redirection, ParseError> {
+        let fd = match self.peek() {
+            Some(Token::IoNumber(fd)) => {
+                let fd = *fd;
+                self.pos += 1;
+                Some(fd)
+            }
+            _ => None,
+        };
+
+        let op = match self.advance() {
+            Some(Token::Operator(op)) => op,
+            Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            None => return Err(ParseError::UnexpectedEof),
+        };
+
+        let target = match self.advance() {
+            Some(Token::HereDoc {
+                delimiter,
+                body,
+                quoted,
+            }) => {
+                let kind = RedirectionKind::HereDoc { expand: !quoted };
+                return Ok(Redirection {
+                    fd: fd.unwrap_or(kind.default_fd()),
+                    kind,
+                    target: Word(body),
+                    delimiter: Some(delimiter),
+                });
+            }
+            Some(Token::Word(word)) => Word(word),
+            Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            None => return Err(ParseError::UnexpectedEof),
+        };
+
+        let kind = REDIRECTIONS
+            .iter()
+            .find(|(spelling, _)| *spelling == op.as_str())
+            .map(|(_, kind)| *kind)
+            .ok_or_else(|| ParseError::UnsupportedRedirection(op.as_str().to_string()))?;
+
+        Ok(Redirection {
+            fd: fd.unwrap_or(kind.default_fd()),
+            kind,
+            target,
+            delimiter: None,
+        })
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+
+        loop {
+            if self.at_redirection() {
+                command.redirections.push(self.redirection()?);
+                continue;
+            }
+
+            let Some(Token::Word(word)) = self.peek() else {
+                break;
+            };
+            let word = word.clone();
+            self.pos += 1;
+
+            if command.words.is_empty()
+                && let Some(assignment) = parse_assignment(&word)
+            {
+                command.assignments.push(assignment);
+            } else {
+                command.words.push(Word(word));
+            }
+        }
+
+        if command.assignments.is_empty()
+            && command.words.is_empty()
+            && command.redirections.is_empty()
+        {
+            return Err(self.unexpected());
+        }
+
+        Ok(command)
+    }
+}
+
+/// Function names are variable names that may also contain `-`, as bash allows
+fn is_function_name(name: &str) -> bool {
+    !name.starts_with('-') && is_valid_name(&name.replace('-', "_"))
+}
+
+/// Split a `NAME=value` word into an assignment
+fn parse_assignment(word: &str) -> Option<Assignment> {
+    let (name, value) = word.split_once('=')?;
+    if !is_valid_name(name) {
+        return None;
+    }
+
+    Some(Assignment {
+        name: name.to_string(),
+        value: Word(value.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redirection::RedirectionKind;
+
+    fn words(command: &Command) -> Vec<&str> {
+        match command {
+            Command::Simple(simple) => simple.words.iter().map(|w| w.0.as_str()).collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn pipes_without_spaces() {
+        let list = parse("ls -l|wc -l").unwrap();
+        let pipeline = &list.items[0].and_or.first;
+
+        assert_eq!(pipeline.commands.len(), 2);
+        assert_eq!(words(&pipeline.commands[0]), ["ls", "-l"]);
+        assert_eq!(words(&pipeline.commands[1]), ["wc", "-l"]);
+    }
+
+    #[test]
+    fn quoted_operators_stay_in_words() {
+        let list = parse(r#"echo "x | y" 'a && b' $(a | b)"#).unwrap();
+        let pipeline = &list.items[0].and_or.first;
+
+        assert_eq!(pipeline.commands.len(), 1);
+        assert_eq!(
+            words(&pipeline.commands[0]),
+            ["echo", r#""x | y""#, "'a && b'", "$(a | b)"]
+        );
+    }
+
+    #[test]
+    fn lists_and_redirections() {
+        let list = parse("make && ./run 2> err.log; echo done").unwrap();
+        assert_eq!(list.items.len(), 2);
+
+        let and_or = &list.items[0].and_or;
+        assert_eq!(and_or.rest.len(), 1);
+        assert_eq!(and_or.rest[0].0, AndOrOperator::And);
+
+        let Command::Simple(run) = &and_or.rest[0].1.commands[0] else {
+            panic!("expected a simple command");
+        };
+        assert_eq!(run.redirections[0].fd, 2);
+        assert_eq!(run.redirections[0].kind, RedirectionKind::Output);
+        assert_eq!(run.redirections[0].target, Word("err.log".to_string()));
+    }
+
+    #[test]
+    fn compound_commands() {
+        let list = parse("if true; then\n  for x in a b; do echo $x; done\nfi").unwrap();
+        let pipeline = &list.items[0].and_or.first;
+
+        assert!(matches!(
+            pipeline.commands[0],
+            Command::Compound(CompoundCommand::If { .. }, _)
+        ));
+
+        let list = parse("(( i < 3 )) && ((echo a) | cat)").unwrap();
+        let and_or = &list.items[0].and_or;
+        assert_eq!(
+            and_or.first.commands[0],
+            Command::Compound(CompoundCommand::Arithmetic(" i < 3 ".to_string()), vec![])
+        );
+        assert!(matches!(
+            and_or.rest[0].1.commands[0],
+            Command::Compound(CompoundCommand::Subshell(_), _)
+        ));
+
+        let list = parse("for ((i = 0; i < 3; i++)); do echo $i; done").unwrap();
+        let Command::Compound(CompoundCommand::ArithmeticFor { init, step, .. }, _) =
+            &list.items[0].and_or.first.commands[0]
+        else {
+            panic!("expected an arithmetic for loop");
+        };
+        assert_eq!((init.as_str(), step.as_str()), ("i = 0", "i++"));
+
+        let list = parse("case $x in a) echo a;& b) echo b;;& *) echo c;; esac").unwrap();
+        let Command::Compound(CompoundCommand::Case { items, .. }, _) =
+            &list.items[0].and_or.first.commands[0]
+        else {
+            panic!("expected a case statement");
+        };
+        let terminators = items.iter().map(|item| item.terminator).collect::<Vec<_>>();
+        assert_eq!(
+            terminators,
+            [
+                CaseTerminator::FallThrough,
+                CaseTerminator::Continue,
+                CaseTerminator::Break
+            ]
+        );
+    }
+
+    #[test]
+    fn function_definitions() {
+        for input in [
+            "greet() { echo hi; }",
+            "function greet { echo hi; }",
+            "function greet()\n{ echo hi; }",
+        ] {
+            let list = parse(input).unwrap();
+            let Command::Function(function) = &list.items[0].and_or.first.commands[0] else {
+                panic!("expected a function definition in {input:?}");
+            };
+            assert_eq!(function.name, "greet");
+            assert!(matches!(function.body, CompoundCommand::BraceGroup(_)));
+        }
+
+        assert_eq!(
+            parse("greet() echo hi"),
+            Err(ParseError::UnexpectedToken("echo".to_string()))
+        );
+        assert!(is_incomplete("greet() {\n  echo hi"));
+    }
+
+    #[test]
+    fn here_documents() {
+        let list = parse("cat <<EOF; cat <<-'END'\n$x\nEOF\n\tquoted $x\n\tEND\n").unwrap();
+        let redirection = |i: usize| match &list.items[i].and_or.first.commands[0] {
+            Command::Simple(simple) => simple.redirections[0].clone(),
+            command => panic!("unexpected command {command:?}"),
+        };
+
+        assert_eq!(
+            redirection(0),
+            Redirection {
+                fd: 0,
+                kind: RedirectionKind::HereDoc { expand: true },
+                target: Word("$x\n".to_string()),
+                delimiter: Some("EOF".to_string()),
+            }
+        );
+        assert_eq!(
+            redirection(1),
+            Redirection {
+                fd: 0,
+                kind: RedirectionKind::HereDoc { expand: false },
+                target: Word("quoted $x\n".to_string()),
+                delimiter: Some("END".to_string()),
+            }
+        );
+        assert!(is_incomplete("cat <<EOF\nbody"));
+    }
+
+    #[test]
+    fn here_documents_display_with_their_bodies() {
+        let input = "f() { cat <<X; cat <<'Y' | wc -l\n$a\nX\nb\nY\n}";
+        let list = parse(input).unwrap();
+        let shown = list.to_string();
+        assert_eq!(shown, "f () { cat <<X\n$a\nX\ncat <<'Y' | wc -l\nb\nY\n }");
+        assert_eq!(parse(&shown), Ok(list));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(parse("echo 'unterminated"), Err(ParseError::UnexpectedEof));
+        assert_eq!(
+            parse("| ls"),
+            Err(ParseError::UnexpectedToken("|".to_string()))
+        );
+        assert_eq!(parse("if true; then"), Err(ParseError::UnexpectedEof));
+    }
+}
@@ -1,16 +1,373 @@
-/// Enum representing different types of output redirection
-#[derive(Clone, Copy)]
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write, stderr, stdout};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use crate::executor::exit_if_fatal;
+use crate::expansion::{expand_heredoc, expand_word};
+use crate::parser::ast::Redirection;
+use crate::shell::Shell;
+use crate::utils::io_error_message;
+
+/// Enum representing different types of redirection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RedirectionKind {
-    Stdout,
-    Stderr,
-    AppendStdout,
-    AppendStderr,
-}
-
-/// Standard redirections with their operators and kinds
-pub const REDIRECTIONS: &[(&[&str], RedirectionKind)] = &[
-    (&[">", "1>"], RedirectionKind::Stdout),
-    (&["2>"], RedirectionKind::Stderr),
-    (&[">>", "1>>"], RedirectionKind::AppendStdout),
-    (&["2>>"], RedirectionKind::AppendStderr),
+    /// `> file`
+    Output,
+    /// `>| file`, overwriting the file even with `noclobber` set
+    Clobber,
+    /// `>> file`
+    Append,
+    /// `< file`
+    Input,
+    /// `<> file`, opened for reading and writing
+    ReadWrite,
+    /// `>&n` duplicates an output descriptor, `>&-` closes one
+    DupOutput,
+    /// `<&n` duplicates an input descriptor, `<&-` closes one
+    DupInput,
+    /// `&> file`, for both standard output and standard error
+    OutputBoth,
+    /// `&>> file`
+    AppendBoth,
+    /// `<< delimiter`, whose target holds the body; only expanded if the delimiter was unquoted
+    HereDoc { expand: bool },
+    /// `<<< word`
+    HereString,
+}
+
+impl RedirectionKind {
+    /// Descriptor redirected when the operator is not preceded by a number
+    pub fn default_fd(self) -> u32 {
+        match self {
+            RedirectionKind::Input
+            | RedirectionKind::ReadWrite
+            | RedirectionKind::DupInput
+            | RedirectionKind::HereDoc { .. }
+            | RedirectionKind::HereString => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// Redirection operators with their kinds
+pub const REDIRECTIONS: &[(&str, RedirectionKind)] = &[
+    (">", RedirectionKind::Output),
+    (">|", RedirectionKind::Clobber),
+    (">>", RedirectionKind::Append),
+    ("<", RedirectionKind::Input),
+    ("<>", RedirectionKind::ReadWrite),
+    (">&", RedirectionKind::DupOutput),
+    ("<&", RedirectionKind::DupInput),
+    ("&>", RedirectionKind::OutputBoth),
+    ("&>>", RedirectionKind::AppendBoth),
+    ("<<<", RedirectionKind::HereString),
 ];
+
+/// A change to one file descriptor, prepared before any of a command's redirections is applied
+#[derive(Debug)]
+enum FdAction {
+    /// Make `fd` refer to an opened file or pipe
+    Open {
+        fd: RawFd,
+        file: OwnedFd,
+    },
+    /// Make `fd` a copy of `source`
+    Duplicate {
+        fd: RawFd,
+        source: RawFd,
+    },
+    Close(RawFd),
+}
+
+/// The opened redirections of one command, applied left to right
+#[derive(Debug, Default)]
+pub struct Redirections(Vec<FdAction>);
+
+impl Redirections {
+    /// Expand targets and open files for a command's redirections, without applying them yet
+    pub fn open(redirections: &[Redirection], shell: &mut Shell) -> Result<Self, String> {
+        let mut actions = Vec::new();
+        let noclobber = shell.options.noclobber;
+
+        for redirection in redirections {
+            let fd = redirection.fd as RawFd;
+            let target = &redirection.target;
+            let expand = |shell: &mut Shell| {
+                expand_word(shell, target).map_err(|err| {
+                    exit_if_fatal(&err, shell);
+                    err.to_string()
+                })
+            };
+
+            match redirection.kind {
+                RedirectionKind::HereDoc { expand } => {
+                    let text = match expand {
+                        true => expand_heredoc(shell, &target.0).map_err(|err| {
+                            exit_if_fatal(&err, shell);
+                            err.to_string()
+                        })?,
+                        false => target.0.clone(),
+                    };
+                    actions.push(FdAction::Open {
+                        fd,
+                        file: text_file(text)?,
+                    });
+                }
+                RedirectionKind::HereString => {
+                    let text = expand(shell)? + "\n";
+                    actions.push(FdAction::Open {
+                        fd,
+                        file: text_file(text)?,
+                    });
+                }
+                RedirectionKind::DupOutput | RedirectionKind::DupInput => {
+                    let word = expand(shell)?;
+                    if word == "-" {
+                        actions.push(FdAction::Close(fd));
+                    } else if let Ok(source) = word.parse::<RawFd>() {
+                        actions.push(FdAction::Duplicate { fd, source });
+                    } else if redirection.kind == RedirectionKind::DupOutput && fd == 1 {
+                        // `>&file` is an old spelling of `&>file`
+                        let file = open_file(&word, RedirectionKind::OutputBoth, noclobber)?;
+                        actions.push(FdAction::Open { fd: 1, file });
+                        actions.push(FdAction::Duplicate { fd: 2, source: 1 });
+                    } else {
+                        return Err(format!("{word}: ambiguous redirect"));
+                    }
+                }
+                kind @ (RedirectionKind::OutputBoth | RedirectionKind::AppendBoth) => {
+                    let file = open_file(&expand(shell)?, kind, noclobber)?;
+                    actions.push(FdAction::Open { fd: 1, file });
+                    actions.push(FdAction::Duplicate { fd: 2, source: 1 });
+                }
+                kind => {
+                    let file = open_file(&expand(shell)?, kind, noclobber)?;
+                    actions.push(FdAction::Open { fd, file });
+                }
+            }
+        }
+
+        // A file opened on a low descriptor could already sit on the descriptor it is
+        // meant for, or on one a later redirection replaces, so each is moved out of the
+        // way of descriptors scripts name
+        for action in &mut actions {
+            if let FdAction::Open { file, .. } = action {
+                let moved = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 10) };
+                if moved == -1 {
+                    let err = io::Error::last_os_error();
+                    return Err(format!("{}: {}", action.fd(), io_error_message(&err)));
+                }
+                *file = unsafe { OwnedFd::from_raw_fd(moved) };
+            }
+        }
+
+        Ok(Self(actions))
+    }
+
+    /// Apply the redirections to the shell's own descriptors, as for builtins; the
+    /// returned guard puts the original descriptors back when dropped
+    pub fn apply(self) -> Result<SavedFds, String> {
+        // Output buffered so far belongs to the descriptors being replaced
+        let _ = stdout().flush();
+        let _ = stderr().flush();
+
+        let mut saved = SavedFds(Vec::new());
+        for action in &self.0 {
+            let fd = action.fd();
+            if !saved.0.iter().any(|&(saved_fd, _)| saved_fd == fd) {
+                saved.0.push((fd, save_fd(fd)));
+            }
+
+            action
+                .apply()
+                .map_err(|err| format!("{}: {}", action.source_fd(), io_error_message(&err)))?;
+        }
+
+        Ok(saved)
+    }
+
+    /// Apply the redirections in a forked child before it executes a program; this only
+    /// makes system calls, so it is safe to run between `fork` and `exec`
+    pub fn apply_in_child(&self) -> io::Result<()> {
+        self.0.iter().try_for_each(FdAction::apply)
+    }
+
+    /// Connect standard input and output to the ends of pipeline pipes before the
+    /// command's own redirections, which can still override them
+    pub fn with_pipes(mut self, stdin: Option<OwnedFd>, stdout: Option<OwnedFd>) -> Self {
+        let pipes = [(0, stdin), (1, stdout)]
+            .into_iter()
+            .filter_map(|(fd, file)| Some(FdAction::Open { fd, file: file? }));
+        self.0.splice(0..0, pipes);
+        self
+    }
+}
+
+impl FdAction {
+    /// Descriptor the action changes
+    fn fd(&self) -> RawFd {
+        match *self {
+            FdAction::Open { fd, .. } | FdAction::Duplicate { fd, .. } | FdAction::Close(fd) => fd,
+        }
+    }
+
+    /// Descriptor named in the error when applying the action fails
+    fn source_fd(&self) -> RawFd {
+        match *self {
+            FdAction::Duplicate { source, .. } => source,
+            _ => self.fd(),
+        }
+    }
+
+    fn apply(&self) -> io::Result<()> {
+        let result = match *self {
+            FdAction::Open { fd, ref file } => unsafe { libc::dup2(file.as_raw_fd(), fd) },
+            // Duplicating a descriptor onto itself only checks that it is open
+            FdAction::Duplicate { fd, source } if fd == source => unsafe {
+                libc::fcntl(fd, libc::F_GETFD)
+            },
+            FdAction::Duplicate { fd, source } => unsafe { libc::dup2(source, fd) },
+            FdAction::Close(fd) => {
+                unsafe { libc::close(fd) };
+                0
+            }
+        };
+
+        match result {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Copies of descriptors replaced by [`Redirections::apply`], restored on drop
+#[derive(Debug)]
+pub struct SavedFds(Vec<(RawFd, Option<RawFd>)>);
+
+impl Drop for SavedFds {
+    fn drop(&mut self) {
+        let _ = stdout().flush();
+        let _ = stderr().flush();
+
+        for &(fd, copy) in self.0.iter().rev() {
+            unsafe {
+                match copy {
+                    Some(copy) => {
+                        libc::dup2(copy, fd);
+                        libc::close(copy);
+                    }
+                    None => {
+                        libc::close(fd);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Keep a close-on-exec copy of a descriptor above the range scripts normally use,
+/// or `None` if it is not open
+fn save_fd(fd: RawFd) -> Option<RawFd> {
+    match unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10) } {
+        -1 => None,
+        copy => Some(copy),
+    }
+}
+
+/// Open the target file of a redirection in the mode its kind calls for; with `noclobber`,
+/// plain output redirections may only create files or write to ones that are not regular
+fn open_file(path: &str, kind: RedirectionKind, noclobber: bool) -> Result<OwnedFd, String> {
+    let error = |err: io::Error| format!("{path}: {}", io_error_message(&err));
+    let mut options = OpenOptions::new();
+    match kind {
+        RedirectionKind::Input => options.read(true),
+        RedirectionKind::ReadWrite => options.read(true).write(true).create(true),
+        RedirectionKind::Append | RedirectionKind::AppendBoth => options.append(true).create(true),
+        RedirectionKind::Output | RedirectionKind::OutputBoth if noclobber => {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(file) => return Ok(OwnedFd::from(file)),
+                Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(error(err)),
+                Err(_) => {}
+            }
+            if fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+                return Err(format!("{path}: cannot overwrite existing file"));
+            }
+            // Devices and pipes such as /dev/null are not truncated
+            options.write(true)
+        }
+        _ => options.write(true).create(true).truncate(true),
+    };
+
+    options.open(path).map(OwnedFd::from).map_err(error)
+}
+
+/// Unlinked temporary file holding `text`, positioned at its start. A file rather than
+/// a pipe, so that text larger than the pipe buffer is written without waiting for the
+/// command to read it
+fn text_file(text: String) -> Result<OwnedFd, String> {
+    let error = |err: io::Error| {
+        format!(
+            "cannot create temp file for here-document: {}",
+            io_error_message(&err)
+        )
+    };
+    let dir = std::env::temp_dir();
+
+    let (mut file, path) = (0..)
+        .find_map(|attempt: u32| {
+            let path = dir.join(format!("rsh-{}-{attempt}", std::process::id()));
+            match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => None,
+                opened => Some(opened.map(|file| (file, path))),
+            }
+        })
+        .unwrap()
+        .map_err(error)?;
+    // Nothing else needs the name, so the file goes away once the command closes it
+    let _ = fs::remove_file(path);
+
+    file.write_all(text.as_bytes()).map_err(error)?;
+    file.seek(SeekFrom::Start(0)).map_err(error)?;
+    Ok(OwnedFd::from(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::capture_output;
+    use crate::parser::parse;
+
+    #[test]
+    fn here_strings_larger_than_a_pipe_reach_forked_stages() -> io::Result<()> {
+        let mut shell = Shell::new(Vec::new());
+        let text = "x".repeat(100_000);
+        shell.variables.set("big", text).unwrap();
+
+        let list = parse("cat <<< \"$big\" | { cat; } | wc -c").unwrap();
+        let (output, status) = capture_output(&list, &mut shell)?;
+        assert_eq!(String::from_utf8_lossy(&output).trim(), "100001");
+        assert_eq!(status, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn redirections_to_new_descriptors_stay_open() -> io::Result<()> {
+        let mut shell = Shell::new(Vec::new());
+        let path = std::env::temp_dir().join(format!("rsh-fd3-{}", std::process::id()));
+        shell.variables.set("f", path.to_string_lossy()).unwrap();
+
+        let list = parse("{ echo group >&3; } 3>$f; sh -c 'echo external >&3' 3>>$f").unwrap();
+        crate::executor::execute_list(&list, &mut shell)?;
+        let written = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(written?, "group\nexternal\n");
+        assert_eq!(shell.last_status, 0);
+        Ok(())
+    }
+}
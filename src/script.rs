@@ -0,0 +1,93 @@
+use std::io::{self, ErrorKind, Read};
+
+use crate::executor::{run_exit_trap, run_list};
+use crate::parser::{is_incomplete, parse};
+use crate::shell::Shell;
+
+/// Run the commands of a script, a `-c` command or standard input until the input ends,
+/// `exit` runs or a syntax error is found, then run the EXIT trap and return the status
+/// the shell exits with. Syntax errors name the script, if it is not standard input
+pub fn run_script(
+    mut input: impl Read,
+    script: Option<&str>,
+    shell: &mut Shell,
+) -> io::Result<i32> {
+    let mut source = String::new();
+    let mut line_number = 0;
+    // Line a command started on, for syntax errors in commands spanning several lines
+    let mut start = 1;
+
+    loop {
+        let line = read_line(&mut input)?;
+        if let Some(line) = &line {
+            line_number += 1;
+            if source.is_empty() {
+                start = line_number;
+            }
+            source.push_str(line);
+            if is_incomplete(&source) {
+                source.push('\n');
+                continue;
+            }
+        }
+
+        // What is left at the end of input is run too, so an unfinished command is
+        // reported as a syntax error
+        if line.is_some() || !source.trim().is_empty() {
+            match parse(&source) {
+                Ok(list) => run_list(&list, shell)?,
+                Err(err) => {
+                    match script {
+                        Some(script) => eprintln!("rsh: {script}: line {start}: {err}"),
+                        None => eprintln!("rsh: line {start}: {err}"),
+                    }
+                    shell.last_status = 2;
+                    break;
+                }
+            }
+            source.clear();
+        }
+
+        if line.is_none() {
+            break;
+        }
+    }
+
+    run_exit_trap(shell);
+    Ok(shell.last_status)
+}
+
+/// Read a line without its newline, or `None` at the end of input. Bytes are read one at
+/// a time, so that commands sharing the input start reading where the shell stopped
+fn read_line(input: &mut impl Read) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let mut byte = [0];
+
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) if line.is_empty() => return Ok(None),
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lines_without_newlines() -> io::Result<()> {
+        let mut input = "echo a\n\nfi".as_bytes();
+        assert_eq!(read_line(&mut input)?.as_deref(), Some("echo a"));
+        assert_eq!(read_line(&mut input)?.as_deref(), Some(""));
+        assert_eq!(read_line(&mut input)?.as_deref(), Some("fi"));
+        assert_eq!(read_line(&mut input)?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use pathsearch::PathSearcher;
+use pathsearch::unix::is_executable;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::jobs::{Jobs, Terminal};
+use crate::parser::ast::FunctionDefinition;
+use crate::traps::Traps;
+use crate::variables::Variables;
+
+/// Options toggled with `set -o name` and `set +o name`
+#[derive(Default)]
+pub struct Options {
+    /// Refuse to let `>` overwrite existing regular files; `>|` still can
+    pub noclobber: bool,
+    /// Disable pathname expansion
+    pub noglob: bool,
+    /// Take a pipeline's status from its last failing command instead of its last command
+    pub pipefail: bool,
+}
+
+impl Options {
+    /// Names of all options, in the order `set -o` lists them
+    pub const NAMES: &[&str] = &["noclobber", "noglob", "pipefail"];
+
+    /// Current value of the option with the given name
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "noclobber" => Some(self.noclobber),
+            "noglob" => Some(self.noglob),
+            "pipefail" => Some(self.pipefail),
+            _ => None,
+        }
+    }
+
+    /// Change the option with the given name, returning false if there is no such option
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        let flag = match name {
+            "noclobber" => &mut self.noclobber,
+            "noglob" => &mut self.noglob,
+            "pipefail" => &mut self.pipefail,
+            _ => return false,
+        };
+        *flag = value;
+        true
+    }
+}
+
+/// Options toggled with `shopt -s name` and `shopt -u name`
+#[derive(Default)]
+pub struct Shopt {
+    /// Let wildcards match names starting with `.`
+    pub dotglob: bool,
+    /// Recognise `?(...)`, `*(...)`, `+(...)`, `@(...)` and `!(...)` in patterns
+    pub extglob: bool,
+    /// Report an error instead of running a command when a pattern matches no files
+    pub failglob: bool,
+    /// Let `**` match any number of directories in pathname expansion
+    pub globstar: bool,
+    /// Match pathnames regardless of case
+    pub nocaseglob: bool,
+    /// Remove patterns that match no files instead of keeping them literally
+    pub nullglob: bool,
+}
+
+impl Shopt {
+    /// Names of all options, in the order `shopt` lists them
+    pub const NAMES: &[&str] = &[
+        "dotglob",
+        "extglob",
+        "failglob",
+        "globstar",
+        "nocaseglob",
+        "nullglob",
+    ];
+
+    /// Current value of the option with the given name
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "dotglob" => Some(self.dotglob),
+            "extglob" => Some(self.extglob),
+            "failglob" => Some(self.failglob),
+            "globstar" => Some(self.globstar),
+            "nocaseglob" => Some(self.nocaseglob),
+            "nullglob" => Some(self.nullglob),
+            _ => None,
+        }
+    }
+
+    /// Change the option with the given name, returning false if there is no such option
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        let flag = match name {
+            "dotglob" => &mut self.dotglob,
+            "extglob" => &mut self.extglob,
+            "failglob" => &mut self.failglob,
+            "globstar" => &mut self.globstar,
+            "nocaseglob" => &mut self.nocaseglob,
+            "nullglob" => &mut self.nullglob,
+            _ => return false,
+        };
+        *flag = value;
+        true
+    }
+}
+
+/// A change of control flow that stops the commands after it, on its way out to the
+/// construct it applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Leave this many enclosing loops
+    Break(usize),
+    /// Leave this many enclosing loops less one, then start the next iteration of that one
+    Continue(usize),
+    /// A foreground job was interrupted with Ctrl-C, which abandons the whole command line
+    Interrupt,
+    /// `return` leaves the running function, whose status is already the last status
+    Return,
+}
+
+/// State carried by a shell session between commands
+#[derive(Default)]
+pub struct Shell {
+    /// Commands entered in this session, including those loaded from HISTFILE
+    pub history: Vec<String>,
+    /// Exit status of the most recently executed pipeline
+    pub last_status: i32,
+    /// Exit status of every command in the most recently executed pipeline
+    pub pipestatus: Vec<i32>,
+    /// Exit status of the last command substitution in the command being expanded
+    pub substitution_status: Option<i32>,
+    /// Process ID of the most recently started background job, for `$!`
+    pub last_background: Option<i32>,
+    /// Jobs started in the background, or stopped, and not yet reported as finished
+    pub jobs: Jobs,
+    /// Terminal handed to foreground jobs, when the shell has job control
+    pub terminal: Option<Terminal>,
+    /// Actions set with `trap`
+    pub traps: Traps,
+    /// Shell functions by name
+    pub functions: HashMap<String, Rc<FunctionDefinition>>,
+    /// Name the shell was started as, or the script or `-c` name it was given, for `$0`
+    pub name: String,
+    /// Positional parameters `$1`, `$2` and so on
+    pub positional: Vec<String>,
+    /// Number of function calls the command being run is nested in
+    pub function_depth: usize,
+    /// Number of loops the command being run is nested in, within the running function
+    pub loop_depth: usize,
+    /// A `break`, `continue`, `return` or interrupt that stops the commands still to run
+    pub flow: Option<Flow>,
+    /// Whether the commands being run are a condition, whose failure does not trigger
+    /// the ERR trap
+    pub in_condition: bool,
+    /// Whether the shell reads commands from a terminal, rather than running a script
+    /// or a `-c` command
+    pub interactive: bool,
+    /// Whether this is a forked copy of the shell, which must not touch history on exit
+    pub subshell: bool,
+    pub options: Options,
+    pub shopt: Shopt,
+    pub variables: Variables,
+}
+
+impl Shell {
+    /// Create a shell session with preloaded history
+    pub fn new(history: Vec<String>) -> Self {
+        Self {
+            history,
+            variables: Variables::from_env(),
+            ..Self::default()
+        }
+    }
+
+    /// Locate an executable, searching the shell's PATH unless the name contains a slash
+    pub fn find_executable(&self, name: &str) -> Option<PathBuf> {
+        if name.contains('/') {
+            let path = PathBuf::from(name);
+            let executable = path.is_file() && is_executable(&path).unwrap_or(false);
+            return executable.then_some(path);
+        }
+
+        let path = self.variables.get("PATH")?;
+        PathSearcher::new(name, Some(OsStr::new(path)), None).next()
+    }
+}
@@ -0,0 +1,192 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use libc::{SIG_DFL, SIG_IGN, SIGINT, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU, c_int};
+
+/// Signals the terminal sends for Ctrl-C, Ctrl-\ and Ctrl-Z, which are meant for the
+/// foreground job rather than an interactive shell
+const KEYBOARD: &[c_int] = &[SIGINT, SIGQUIT, SIGTSTP];
+
+/// Signals sent for using the terminal from a background process group, which would
+/// stop the shell while it hands the terminal to jobs and takes it back
+const TERMINAL_ACCESS: &[c_int] = &[SIGTTIN, SIGTTOU];
+
+/// Signal names without their `SIG` prefix
+pub const NAMES: &[(&str, c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+/// One more than the highest signal number the shell keeps track of
+const SIGNAL_LIMIT: usize = 65;
+
+/// Signals caught since the shell last ran their traps
+static PENDING: [AtomicBool; SIGNAL_LIMIT] = [const { AtomicBool::new(false) }; SIGNAL_LIMIT];
+
+/// Signals a trap with an empty action ignores, as a bit set; children inherit this
+static IGNORED: AtomicU64 = AtomicU64::new(0);
+
+/// Name of a signal without its `SIG` prefix
+pub fn name(signal: c_int) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|&&(_, s)| s == signal)
+        .map(|&(name, _)| name)
+}
+
+/// Look up a signal by number or by name, with or without the `SIG` prefix and in any case
+pub fn parse(spec: &str) -> Option<c_int> {
+    if let Ok(signal) = spec.parse::<c_int>() {
+        return name(signal).map(|_| signal);
+    }
+
+    let spec = spec.to_ascii_uppercase();
+    let spec = spec.strip_prefix("SIG").unwrap_or(&spec);
+    NAMES
+        .iter()
+        .find(|&&(name, _)| name == spec)
+        .map(|&(_, s)| s)
+}
+
+/// Ignore the signals an interactive shell must survive
+pub fn ignore_interactive() {
+    for &signal in KEYBOARD.iter().chain(TERMINAL_ACCESS) {
+        unsafe { libc::signal(signal, SIG_IGN) };
+    }
+}
+
+/// Give a child that is about to run a program the default behaviour for every signal
+/// the shell ignores, since ignored signals stay ignored across `exec`; signals ignored
+/// with `trap` stay ignored. This only makes async-signal-safe calls, so it can run
+/// between `fork` and `exec`
+pub fn restore_defaults() {
+    for &signal in KEYBOARD.iter().chain(TERMINAL_ACCESS) {
+        if !is_ignored(signal) {
+            unsafe { libc::signal(signal, SIG_DFL) };
+        }
+    }
+}
+
+/// Let Ctrl-C and Ctrl-\ end a forked copy of the shell; stopping signals stay ignored
+/// as it has no job control to recover from being stopped
+pub fn restore_interrupts() {
+    for signal in [SIGINT, SIGQUIT] {
+        if !is_ignored(signal) {
+            unsafe { libc::signal(signal, SIG_DFL) };
+        }
+    }
+}
+
+/// Record the signal for the shell to run its trap at the next safe point
+extern "C" fn record(signal: c_int) {
+    if let Some(pending) = PENDING.get(signal as usize) {
+        pending.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Catch a signal so that its trap runs, restarting system calls it interrupts
+pub fn catch(signal: c_int) -> io::Result<()> {
+    let handler: extern "C" fn(c_int) = record;
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = handler as libc::sighandler_t;
+    action.sa_flags = libc::SA_RESTART;
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+
+    match unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => {
+            set_ignored(signal, false);
+            Ok(())
+        }
+    }
+}
+
+/// Ignore a signal in the shell and in the commands it runs
+pub fn ignore(signal: c_int) -> io::Result<()> {
+    match unsafe { libc::signal(signal, SIG_IGN) } {
+        libc::SIG_ERR => Err(io::Error::last_os_error()),
+        _ => {
+            set_ignored(signal, true);
+            Ok(())
+        }
+    }
+}
+
+/// Give a signal back the behaviour it had when the shell started: ignored if it is one
+/// an interactive shell must survive, otherwise the default
+pub fn reset(signal: c_int, interactive: bool) {
+    let interactive_ignored = KEYBOARD.iter().chain(TERMINAL_ACCESS).any(|&s| s == signal);
+    let handler = match interactive && interactive_ignored {
+        true => SIG_IGN,
+        false => SIG_DFL,
+    };
+    unsafe { libc::signal(signal, handler) };
+    set_ignored(signal, false);
+    if let Some(pending) = PENDING.get(signal as usize) {
+        pending.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Signals caught since the last call, in increasing order
+pub fn take_pending() -> Vec<c_int> {
+    (1..SIGNAL_LIMIT)
+        .filter(|&signal| PENDING[signal].swap(false, Ordering::SeqCst))
+        .map(|signal| signal as c_int)
+        .collect()
+}
+
+fn is_ignored(signal: c_int) -> bool {
+    (0..64).contains(&signal) && IGNORED.load(Ordering::SeqCst) & (1 << signal) != 0
+}
+
+fn set_ignored(signal: c_int, ignored: bool) {
+    if (0..64).contains(&signal) {
+        match ignored {
+            true => IGNORED.fetch_or(1 << signal, Ordering::SeqCst),
+            false => IGNORED.fetch_and(!(1 << signal), Ordering::SeqCst),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_signal_specs() {
+        assert_eq!(parse("INT"), Some(libc::SIGINT));
+        assert_eq!(parse("sigterm"), Some(libc::SIGTERM));
+        assert_eq!(parse("SIGHUP"), Some(libc::SIGHUP));
+        assert_eq!(parse("9"), Some(libc::SIGKILL));
+        assert_eq!(parse("0"), None);
+        assert_eq!(parse("NOPE"), None);
+        assert_eq!(name(libc::SIGUSR1), Some("USR1"));
+    }
+}
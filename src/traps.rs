@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+use libc::c_int;
+
+use crate::signals;
+
+/// Something `trap` can attach an action to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trap {
+    /// The shell exiting
+    Exit,
+    Signal(c_int),
+    /// Before each simple command
+    Debug,
+    /// A command failing outside of a condition
+    Err,
+    /// A shell function returning
+    Return,
+}
+
+impl Trap {
+    /// Parse a signal specification as `trap` accepts it, including the pseudo-signals
+    pub fn parse(spec: &str) -> Option<Trap> {
+        match spec.to_ascii_uppercase().as_str() {
+            "EXIT" | "0" => Some(Trap::Exit),
+            "DEBUG" => Some(Trap::Debug),
+            "ERR" => Some(Trap::Err),
+            "RETURN" => Some(Trap::Return),
+            _ => signals::parse(spec).map(Trap::Signal),
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trap::Exit => write!(f, "EXIT"),
+            Trap::Signal(signal) => write!(f, "SIG{}", signals::name(*signal).unwrap_or("?")),
+            Trap::Debug => write!(f, "DEBUG"),
+            Trap::Err => write!(f, "ERR"),
+            Trap::Return => write!(f, "RETURN"),
+        }
+    }
+}
+
+/// Actions set with `trap`; an empty action ignores the signal
+#[derive(Default)]
+pub struct Traps {
+    actions: BTreeMap<Trap, String>,
+    /// Whether a trap action is running, during which DEBUG, ERR and RETURN do not fire
+    pub running: bool,
+}
+
+impl Traps {
+    /// Action set for a trap, if any
+    pub fn get(&self, trap: Trap) -> Option<&str> {
+        self.actions.get(&trap).map(String::as_str)
+    }
+
+    /// Every trap with an action, in the order `trap -p` lists them
+    pub fn iter(&self) -> impl Iterator<Item = (Trap, &str)> {
+        self.actions
+            .iter()
+            .map(|(&trap, action)| (trap, action.as_str()))
+    }
+
+    /// Set the action for a trap, catching or ignoring the signal it stands for
+    pub fn set(&mut self, trap: Trap, action: String) -> io::Result<()> {
+        if let Trap::Signal(signal) = trap {
+            match action.is_empty() {
+                true => signals::ignore(signal)?,
+                false => signals::catch(signal)?,
+            }
+        }
+        self.actions.insert(trap, action);
+        Ok(())
+    }
+
+    /// Remove the action for a trap, giving its signal back the shell's initial behaviour
+    pub fn reset(&mut self, trap: Trap, interactive: bool) {
+        if let Trap::Signal(signal) = trap {
+            signals::reset(signal, interactive);
+        }
+        self.actions.remove(&trap);
+    }
+
+    /// Remove the action for a trap so that it runs only once, as the EXIT trap does
+    pub fn take(&mut self, trap: Trap) -> Option<String> {
+        self.actions.remove(&trap)
+    }
+
+    /// Set aside the DEBUG, ERR and RETURN traps while a function runs, as functions do
+    /// not inherit them
+    pub fn suspend_for_function(&mut self) -> Vec<(Trap, String)> {
+        [Trap::Debug, Trap::Err, Trap::Return]
+            .into_iter()
+            .filter_map(|trap| Some((trap, self.actions.remove(&trap)?)))
+            .collect()
+    }
+
+    /// Put back traps set aside for a function, unless the function set them itself
+    pub fn resume_after_function(&mut self, suspended: Vec<(Trap, String)>) {
+        for (trap, action) in suspended {
+            self.actions.entry(trap).or_insert(action);
+        }
+    }
+
+    /// Reset traps for a forked copy of the shell: ignored signals stay ignored, while
+    /// other actions belong to the parent and do not run in the copy
+    pub fn clear_for_subshell(&mut self) {
+        let actions = std::mem::take(&mut self.actions);
+        for (trap, action) in actions {
+            match action.is_empty() {
+                true => {
+                    self.actions.insert(trap, action);
+                }
+                false => self.reset(trap, false),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pseudo_signals() {
+        assert_eq!(Trap::parse("EXIT"), Some(Trap::Exit));
+        assert_eq!(Trap::parse("0"), Some(Trap::Exit));
+        assert_eq!(Trap::parse("err"), Some(Trap::Err));
+        assert_eq!(Trap::parse("INT"), Some(Trap::Signal(libc::SIGINT)));
+        assert_eq!(Trap::parse("BOGUS"), None);
+        assert_eq!(Trap::Signal(libc::SIGTERM).to_string(), "SIGTERM");
+    }
+}
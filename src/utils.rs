@@ -1,3 +1,6 @@
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
 /// Find the longest common prefix among a list of strings
 pub fn lcp(strings: Vec<String>) -> String {
     if strings.is_empty() {
@@ -18,3 +21,41 @@ pub fn lcp(strings: Vec<String>) -> String {
 
     first[..lcp_len].to_string()
 }
+
+/// Check whether a string is a valid shell variable name
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quote a string so the shell reads it back as a single word
+pub fn shell_quote(value: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c);
+
+    if !value.is_empty() && value.chars().all(is_plain) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Convert a child's exit status into a shell status, mapping signals to 128 + signal number
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+/// Describe an I/O error the way shells do, without Rust's `(os error N)` suffix
+pub fn io_error_message(err: &std::io::Error) -> String {
+    let message = err.to_string();
+    match message.find(" (os error") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::env;
+
+use thiserror::Error;
+
+/// Errors from modifying shell variables
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VariableError {
+    #[error("{0}: readonly variable")]
+    ReadOnly(String),
+}
+
+/// A shell variable with its attributes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variable {
+    /// `None` for variables that were declared (e.g. `export NAME`) but never assigned
+    pub value: Option<String>,
+    pub exported: bool,
+    pub readonly: bool,
+}
+
+/// Store of shell variables, both shell-local and exported
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    vars: HashMap<String, Variable>,
+    /// For each running function, the variables its `local` declarations replaced
+    scopes: Vec<Vec<(String, Option<Variable>)>>,
+}
+
+impl Variables {
+    /// Create a store holding the process environment, with every entry exported
+    pub fn from_env() -> Self {
+        let vars = env::vars()
+            .map(|(name, value)| {
+                let variable = Variable {
+                    value: Some(value),
+                    exported: true,
+                    readonly: false,
+                };
+                (name, variable)
+            })
+            .collect();
+
+        Self {
+            vars,
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Value of a set variable
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name)?.value.as_deref()
+    }
+
+    /// Variable with its attributes, whether or not it has a value
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.vars.get(name)
+    }
+
+    /// Assign a value, keeping the variable's existing attributes
+    pub fn set(&mut self, name: &str, value: impl Into<String>) -> Result<(), VariableError> {
+        let variable = self.vars.entry(name.to_string()).or_default();
+        if variable.readonly {
+            return Err(VariableError::ReadOnly(name.to_string()));
+        }
+
+        variable.value = Some(value.into());
+        Ok(())
+    }
+
+    /// Mark a variable as exported, assigning it first if a value is given
+    pub fn export(&mut self, name: &str, value: Option<String>) -> Result<(), VariableError> {
+        if let Some(value) = value {
+            self.set(name, value)?;
+        }
+
+        self.vars.entry(name.to_string()).or_default().exported = true;
+        Ok(())
+    }
+
+    /// Mark a variable as read-only, assigning it first if a value is given
+    pub fn set_readonly(&mut self, name: &str, value: Option<String>) -> Result<(), VariableError> {
+        if let Some(value) = value {
+            self.set(name, value)?;
+        }
+
+        self.vars.entry(name.to_string()).or_default().readonly = true;
+        Ok(())
+    }
+
+    /// Remove a variable entirely
+    pub fn unset(&mut self, name: &str) -> Result<(), VariableError> {
+        if self
+            .vars
+            .get(name)
+            .is_some_and(|variable| variable.readonly)
+        {
+            return Err(VariableError::ReadOnly(name.to_string()));
+        }
+
+        self.vars.remove(name);
+        Ok(())
+    }
+
+    /// Export assignments for the duration of a single command, returning the
+    /// variables they replaced so they can be put back with [`Variables::restore`]
+    pub fn assign_temporary(
+        &mut self,
+        assignments: Vec<(String, String)>,
+    ) -> Result<Vec<(String, Option<Variable>)>, VariableError> {
+        let mut saved = Vec::with_capacity(assignments.len());
+
+        for (name, value) in assignments {
+            let previous = self.vars.get(&name).cloned();
+            if let Err(err) = self.export(&name, Some(value)) {
+                self.restore(saved);
+                return Err(err);
+            }
+            saved.push((name, previous));
+        }
+
+        Ok(saved)
+    }
+
+    /// Put back variables replaced by [`Variables::assign_temporary`]
+    pub fn restore(&mut self, saved: Vec<(String, Option<Variable>)>) {
+        for (name, previous) in saved.into_iter().rev() {
+            match previous {
+                Some(variable) => self.vars.insert(name, variable),
+                None => self.vars.remove(&name),
+            };
+        }
+    }
+
+    /// Start the scope of a function call, for `local` declarations
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    /// End the scope of a function call, putting back the variables its `local`
+    /// declarations replaced
+    pub fn pop_scope(&mut self) {
+        if let Some(saved) = self.scopes.pop() {
+            self.restore(saved);
+        }
+    }
+
+    /// Declare a variable local to the current function, which the functions it calls
+    /// see too; the variable keeps its export attribute, and is unset without a value
+    pub fn local(&mut self, name: &str, value: Option<String>) -> Result<(), VariableError> {
+        let previous = self.vars.get(name).cloned();
+        if previous.as_ref().is_some_and(|variable| variable.readonly) {
+            return Err(VariableError::ReadOnly(name.to_string()));
+        }
+
+        if let Some(scope) = self.scopes.last_mut()
+            && !scope.iter().any(|(saved, _)| saved == name)
+        {
+            scope.push((name.to_string(), previous.clone()));
+        }
+
+        let variable = Variable {
+            value,
+            exported: previous.is_some_and(|variable| variable.exported),
+            readonly: false,
+        };
+        self.vars.insert(name.to_string(), variable);
+        Ok(())
+    }
+
+    /// All variables sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Variable)> {
+        let mut vars = self
+            .vars
+            .iter()
+            .map(|(name, variable)| (name.as_str(), variable))
+            .collect::<Vec<_>>();
+        vars.sort_unstable_by_key(|&(name, _)| name);
+        vars.into_iter()
+    }
+
+    /// Name and value of every exported variable, forming the environment of child processes
+    pub fn exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars.iter().filter_map(|(name, variable)| {
+            let value = variable.value.as_deref().filter(|_| variable.exported)?;
+            Some((name.as_str(), value))
+        })
+    }
+}